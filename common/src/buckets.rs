@@ -0,0 +1,33 @@
+/// Number of buckets in a king bucket map, indexed by the side to move's
+/// oriented king square.
+///
+/// Panics if a bucket below the largest one is never used, which would leave
+/// weights that are never trained.
+pub fn count(buckets: &[usize; 64]) -> usize {
+    let num_buckets = buckets.iter().max().unwrap() + 1;
+
+    for bucket in 0..num_buckets {
+        assert!(buckets.contains(&bucket), "King bucket {bucket} is unused!");
+    }
+
+    num_buckets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_buckets() {
+        assert_eq!(count(&[0; 64]), 1);
+        assert_eq!(count(&std::array::from_fn(|sq| sq / 16)), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "King bucket 1 is unused!")]
+    fn rejects_unused_buckets() {
+        let mut buckets = [0; 64];
+        buckets[63] = 2;
+        count(&buckets);
+    }
+}
@@ -0,0 +1,339 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::runlog;
+
+/// Name of the entry in the checkpoint directory pointing at the best
+/// checkpoint so far.
+pub const BEST: &str = "best";
+
+/// Which checkpoints keep their full optimiser state. The rest are pruned
+/// down to `quantised.bin`, and the final checkpoint is always kept.
+#[derive(Clone, Copy, Debug)]
+pub struct Retention {
+    pub keep_last: usize,
+    /// By validation loss, when the trainer measures it.
+    pub keep_best: usize,
+}
+
+struct Checkpoint {
+    superbatch: usize,
+    /// Written part way through `superbatch`.
+    interrupted: bool,
+    path: PathBuf,
+    validation_loss: Option<f32>,
+    pruned: bool,
+}
+
+/// Tracks the checkpoints written during a run and applies [`Retention`]
+/// each time one is added.
+pub struct Checkpoints {
+    root: PathBuf,
+    retention: Retention,
+    saved: Vec<Checkpoint>,
+}
+
+impl Checkpoints {
+    pub fn new(root: &str, retention: Retention) -> Self {
+        Self { root: PathBuf::from(root), retention, saved: Vec::new() }
+    }
+
+    /// Picks up the `{prefix}-{superbatch}` and interrupted
+    /// `{prefix}-{superbatch}-interrupted` checkpoints a previous run left in
+    /// `root`, with their validation losses from its log, so that a resumed
+    /// run keeps applying [`Retention`] to them.
+    pub fn open(root: &str, prefix: &str, retention: Retention) -> io::Result<Self> {
+        let mut checkpoints = Self::new(root, retention);
+
+        let log = match runlog::read(root) {
+            Ok(log) => log,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err),
+        };
+
+        for entry in std::fs::read_dir(root)? {
+            let entry = entry?;
+            let name = entry.file_name();
+
+            let Some((superbatch, interrupted)) = name.to_str().and_then(|name| parse_name(name, prefix)) else {
+                continue;
+            };
+
+            if !entry.path().is_dir() {
+                continue;
+            }
+
+            // a superbatch validated more than once was resumed from an earlier checkpoint
+            let validation_loss = if interrupted {
+                None
+            } else {
+                log.iter().rev().filter(|row| row.superbatch == superbatch).find_map(|row| row.validation_loss)
+            };
+
+            let pruned = is_pruned(&entry.path())?;
+            checkpoints.saved.push(Checkpoint { superbatch, interrupted, path: entry.path(), validation_loss, pruned });
+        }
+
+        checkpoints.saved.sort_by_key(Checkpoint::recency);
+        Ok(checkpoints)
+    }
+
+    /// Records a checkpoint that has just been written, prunes the ones that
+    /// are no longer retained and updates [`BEST`].
+    pub fn add(
+        &mut self,
+        superbatch: usize,
+        path: &str,
+        validation_loss: Option<f32>,
+        is_final: bool,
+    ) -> io::Result<()> {
+        let path = PathBuf::from(path);
+        self.saved.push(Checkpoint { superbatch, interrupted: false, path, validation_loss, pruned: false });
+        self.apply(is_final)
+    }
+
+    /// Records a checkpoint written when training was interrupted. It counts
+    /// towards `keep_last` like any other, so it is pruned once enough newer
+    /// checkpoints have been written after resuming from it.
+    pub fn add_interrupted(&mut self, superbatch: usize, path: &str) -> io::Result<()> {
+        let path = PathBuf::from(path);
+        self.saved.push(Checkpoint { superbatch, interrupted: true, path, validation_loss: None, pruned: false });
+        self.apply(false)
+    }
+
+    fn apply(&mut self, is_final: bool) -> io::Result<()> {
+        let keep = self.retained(is_final);
+
+        for (i, checkpoint) in self.saved.iter_mut().enumerate() {
+            if !keep.contains(&i) && !checkpoint.pruned {
+                prune(&checkpoint.path)?;
+                checkpoint.pruned = true;
+            }
+        }
+
+        if let Some(best) = self.best() {
+            let target = best.path.file_name().map(PathBuf::from).unwrap_or_else(|| best.path.clone());
+            point_at(&self.root.join(BEST), &target)?;
+        }
+
+        Ok(())
+    }
+
+    /// Lowest validation loss, or the latest checkpoint if there is none.
+    fn best(&self) -> Option<&Checkpoint> {
+        let scored = self.saved.iter().filter(|c| c.validation_loss.is_some());
+        scored
+            .min_by(|a, b| a.validation_loss.unwrap().total_cmp(&b.validation_loss.unwrap()))
+            .or_else(|| self.saved.iter().max_by_key(|c| c.recency()))
+    }
+
+    /// Indices into `saved` that keep their full state.
+    fn retained(&self, is_final: bool) -> Vec<usize> {
+        let mut by_recency = (0..self.saved.len()).collect::<Vec<_>>();
+        by_recency.sort_by_key(|&i| std::cmp::Reverse(self.saved[i].recency()));
+
+        let mut by_loss =
+            (0..self.saved.len()).filter(|&i| self.saved[i].validation_loss.is_some()).collect::<Vec<_>>();
+        by_loss.sort_by(|&a, &b| {
+            self.saved[a].validation_loss.unwrap().total_cmp(&self.saved[b].validation_loss.unwrap())
+        });
+
+        let mut keep = by_recency.iter().take(self.retention.keep_last).copied().collect::<Vec<_>>();
+        keep.extend(by_loss.iter().take(self.retention.keep_best));
+
+        if is_final {
+            keep.push(self.saved.len() - 1);
+        }
+
+        keep
+    }
+}
+
+impl Checkpoint {
+    fn recency(&self) -> (usize, bool) {
+        (self.superbatch, !self.interrupted)
+    }
+}
+
+/// Superbatch of a checkpoint directory name and whether it was interrupted.
+fn parse_name(name: &str, prefix: &str) -> Option<(usize, bool)> {
+    let rest = name.strip_prefix(prefix)?.strip_prefix('-')?;
+
+    match rest.strip_suffix("-interrupted") {
+        Some(superbatch) => Some((superbatch.parse().ok()?, true)),
+        None => Some((rest.parse().ok()?, false)),
+    }
+}
+
+/// Removes everything in a checkpoint directory except `quantised.bin`.
+fn prune(path: &Path) -> io::Result<()> {
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+
+        if entry.file_name() == "quantised.bin" {
+            continue;
+        }
+
+        if entry.file_type()?.is_dir() {
+            std::fs::remove_dir_all(entry.path())?;
+        } else {
+            std::fs::remove_file(entry.path())?;
+        }
+    }
+
+    Ok(())
+}
+
+fn is_pruned(path: &Path) -> io::Result<bool> {
+    for entry in std::fs::read_dir(path)? {
+        if entry?.file_name() != "quantised.bin" {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Replaces `link` with a symlink to `target`, relative to the link's
+/// directory, or a file containing its name where symlinks are unavailable.
+fn point_at(link: &Path, target: &Path) -> io::Result<()> {
+    let tmp = link.with_extension("tmp");
+    let _ = std::fs::remove_file(&tmp);
+
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(target, &tmp)?;
+
+    #[cfg(not(unix))]
+    std::fs::write(&tmp, target.to_string_lossy().as_bytes())?;
+
+    std::fs::rename(&tmp, link)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    struct Dir(TempDir);
+
+    impl Dir {
+        fn new(name: &str) -> Self {
+            Self(TempDir::new(name))
+        }
+
+        fn root(&self) -> &str {
+            self.0.to_str()
+        }
+
+        /// Writes `policy-{name}` the way the policy trainer lays it out.
+        fn save(&self, name: &str) -> String {
+            let path = self.0.join(format!("policy-{name}"));
+            std::fs::create_dir_all(path.join("optimiser_state")).unwrap();
+
+            for file in ["quantised.bin", "weights.bin", "optimiser_state/momentum.bin"] {
+                std::fs::write(path.join(file), name).unwrap();
+            }
+
+            path.to_str().unwrap().to_string()
+        }
+
+        /// Checkpoints that were not pruned, without the `policy-` prefix.
+        fn full(&self) -> Vec<String> {
+            let mut full = std::fs::read_dir(self.0.path())
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .filter(|path| path.is_dir() && !path.is_symlink() && !is_pruned(path).unwrap())
+                .map(|path| path.file_name().unwrap().to_str().unwrap()["policy-".len()..].to_string())
+                .collect::<Vec<_>>();
+
+            full.sort();
+            full
+        }
+
+        fn best(&self) -> PathBuf {
+            let link = self.0.join(BEST);
+            if cfg!(unix) {
+                std::fs::read_link(link).unwrap()
+            } else {
+                PathBuf::from(std::fs::read_to_string(link).unwrap())
+            }
+        }
+    }
+
+    fn add(dir: &Dir, checkpoints: &mut Checkpoints, superbatch: usize, loss: Option<f32>, is_final: bool) {
+        let path = dir.save(&superbatch.to_string());
+        checkpoints.add(superbatch, &path, loss, is_final).unwrap();
+    }
+
+    #[test]
+    fn keeps_the_last_checkpoints() {
+        let dir = Dir::new("checkpoints-last");
+        let mut checkpoints = Checkpoints::new(dir.root(), Retention { keep_last: 2, keep_best: 3 });
+
+        for superbatch in [10, 20, 30, 40] {
+            add(&dir, &mut checkpoints, superbatch, None, false);
+        }
+
+        assert_eq!(dir.full(), ["30", "40"]);
+        assert_eq!(dir.best(), Path::new("policy-40"));
+
+        // pruned checkpoints keep only the quantised net
+        let pruned = std::fs::read_dir(dir.0.join("policy-10")).unwrap().map(|e| e.unwrap().file_name());
+        assert_eq!(pruned.collect::<Vec<_>>(), ["quantised.bin"]);
+    }
+
+    #[test]
+    fn keeps_the_best_checkpoints() {
+        let dir = Dir::new("checkpoints-best");
+        let mut checkpoints = Checkpoints::new(dir.root(), Retention { keep_last: 1, keep_best: 2 });
+
+        for (superbatch, loss, best) in [(10, 0.5, 10), (20, 0.3, 20), (30, 0.4, 20), (40, 0.6, 20), (50, 0.2, 50)] {
+            add(&dir, &mut checkpoints, superbatch, Some(loss), false);
+            assert_eq!(dir.best(), Path::new(&format!("policy-{best}")));
+        }
+
+        assert_eq!(dir.full(), ["20", "50"]);
+    }
+
+    #[test]
+    fn always_keeps_the_final_checkpoint() {
+        let dir = Dir::new("checkpoints-final");
+        let mut checkpoints = Checkpoints::new(dir.root(), Retention { keep_last: 0, keep_best: 1 });
+
+        add(&dir, &mut checkpoints, 10, Some(0.1), false);
+        add(&dir, &mut checkpoints, 20, Some(0.5), false);
+        assert_eq!(dir.full(), ["10"]);
+
+        add(&dir, &mut checkpoints, 30, Some(0.9), true);
+        assert_eq!(dir.full(), ["10", "30"]);
+        assert_eq!(dir.best(), Path::new("policy-10"));
+    }
+
+    #[test]
+    fn resumes_from_what_is_on_disk() {
+        let dir = Dir::new("checkpoints-resume");
+        let retention = Retention { keep_last: 1, keep_best: 1 };
+        let mut checkpoints = Checkpoints::new(dir.root(), retention);
+        let mut log = runlog::RunLog::open(dir.root()).unwrap();
+
+        for (superbatch, loss) in [(10, 0.3), (20, 0.5), (30, 0.4)] {
+            add(&dir, &mut checkpoints, superbatch, Some(loss), false);
+            let entry = runlog::Entry { superbatch, validation_loss: Some(loss), ..Default::default() };
+            log.write(&entry).unwrap();
+        }
+
+        assert_eq!(dir.full(), ["10", "30"]);
+
+        let interrupted = dir.save("35-interrupted");
+        checkpoints.add_interrupted(35, &interrupted).unwrap();
+        assert_eq!(dir.full(), ["10", "35-interrupted"]);
+
+        let mut checkpoints = Checkpoints::open(dir.root(), "policy", retention).unwrap();
+        add(&dir, &mut checkpoints, 40, Some(0.35), false);
+
+        assert_eq!(dir.full(), ["10", "40"]);
+        assert_eq!(dir.best(), Path::new("policy-10"));
+    }
+}
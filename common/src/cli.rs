@@ -0,0 +1,307 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+    str::FromStr,
+};
+
+/// Minimal command-line parser shared by the binaries. Every option is named
+/// (`--name value` or `--name=value`), flags take no value, and `--help`
+/// prints the generated usage.
+pub struct Cli {
+    name: &'static str,
+    about: &'static str,
+    opts: Vec<Opt>,
+}
+
+struct Opt {
+    name: &'static str,
+    help: &'static str,
+    kind: Kind,
+}
+
+enum Kind {
+    Required,
+    Default(&'static str),
+    Optional,
+    Multiple,
+    Flag,
+}
+
+pub struct Matches {
+    values: HashMap<&'static str, Vec<String>>,
+}
+
+impl Cli {
+    pub fn new(name: &'static str, about: &'static str) -> Self {
+        Self { name, about, opts: Vec::new() }
+    }
+
+    pub fn required(self, name: &'static str, help: &'static str) -> Self {
+        self.with(name, help, Kind::Required)
+    }
+
+    pub fn default(self, name: &'static str, default: &'static str, help: &'static str) -> Self {
+        self.with(name, help, Kind::Default(default))
+    }
+
+    pub fn optional(self, name: &'static str, help: &'static str) -> Self {
+        self.with(name, help, Kind::Optional)
+    }
+
+    /// May be given any number of times.
+    pub fn multiple(self, name: &'static str, help: &'static str) -> Self {
+        self.with(name, help, Kind::Multiple)
+    }
+
+    pub fn flag(self, name: &'static str, help: &'static str) -> Self {
+        self.with(name, help, Kind::Flag)
+    }
+
+    fn with(mut self, name: &'static str, help: &'static str, kind: Kind) -> Self {
+        self.opts.push(Opt { name, help, kind });
+        self
+    }
+
+    pub fn usage(&self) -> String {
+        let mut usage = format!("{}\n\nUsage: {}", self.about, self.name);
+
+        for opt in &self.opts {
+            match opt.kind {
+                Kind::Required => usage += &format!(" --{} <{}>", opt.name, opt.name),
+                Kind::Multiple => usage += &format!(" [--{} <{}>]...", opt.name, opt.name),
+                _ => {}
+            }
+        }
+
+        usage += " [options]\n\nOptions:\n";
+
+        for opt in &self.opts {
+            let arg = match opt.kind {
+                Kind::Flag => format!("--{}", opt.name),
+                _ => format!("--{} <{}>", opt.name, opt.name),
+            };
+
+            let extra = match opt.kind {
+                Kind::Required => " (required)".to_string(),
+                Kind::Default(default) => format!(" [default: {default}]"),
+                _ => String::new(),
+            };
+
+            usage += &format!("  {arg:<32} {}{extra}\n", opt.help);
+        }
+
+        usage + &format!("  {:<32} Print this message\n", "-h, --help")
+    }
+
+    /// Parses the process arguments, exiting on `--help` or on any error.
+    pub fn parse(self) -> Matches {
+        match self.try_parse(std::env::args().skip(1)) {
+            Ok(matches) => matches,
+            Err(None) => {
+                println!("{}", self.usage());
+                std::process::exit(0);
+            }
+            Err(Some(err)) => {
+                eprintln!("error: {err}\n\n{}", self.usage());
+                std::process::exit(2);
+            }
+        }
+    }
+
+    /// `Err(None)` means help was requested.
+    pub fn try_parse(&self, args: impl IntoIterator<Item = String>) -> Result<Matches, Option<String>> {
+        let mut values: HashMap<&'static str, Vec<String>> = HashMap::new();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            if arg == "-h" || arg == "--help" {
+                return Err(None);
+            }
+
+            let Some(stripped) = arg.strip_prefix("--") else {
+                return Err(Some(format!("unexpected argument '{arg}'")));
+            };
+
+            let (name, inline) = match stripped.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (stripped, None),
+            };
+
+            let Some(opt) = self.opts.iter().find(|opt| opt.name == name) else {
+                return Err(Some(format!("unknown option '--{name}'")));
+            };
+
+            let value = match (&opt.kind, inline) {
+                (Kind::Flag, None) => String::new(),
+                (Kind::Flag, Some(_)) => return Err(Some(format!("'--{name}' does not take a value"))),
+                (_, Some(value)) => value,
+                (_, None) => args.next().ok_or_else(|| Some(format!("missing value for '--{name}'")))?,
+            };
+
+            let entry = values.entry(opt.name).or_default();
+
+            if !matches!(opt.kind, Kind::Multiple) && !entry.is_empty() {
+                return Err(Some(format!("'--{name}' given more than once")));
+            }
+
+            entry.push(value);
+        }
+
+        for opt in &self.opts {
+            match opt.kind {
+                Kind::Required if !values.contains_key(opt.name) => {
+                    return Err(Some(format!("missing required option '--{}'", opt.name)));
+                }
+                Kind::Default(default) => {
+                    values.entry(opt.name).or_insert_with(|| vec![default.to_string()]);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Matches { values })
+    }
+}
+
+impl Matches {
+    /// Value of a required or defaulted option.
+    pub fn get(&self, name: &str) -> &str {
+        self.get_opt(name).unwrap_or_else(|| panic!("'--{name}' is not a required option!"))
+    }
+
+    pub fn get_opt(&self, name: &str) -> Option<&str> {
+        self.values.get(name).and_then(|vals| vals.first()).map(String::as_str)
+    }
+
+    pub fn get_all(&self, name: &str) -> &[String] {
+        self.values.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        self.values.contains_key(name)
+    }
+
+    pub fn parse<T: FromStr>(&self, name: &str) -> T
+    where
+        T::Err: Display,
+    {
+        parse_value(name, self.get(name))
+    }
+
+    pub fn parse_opt<T: FromStr>(&self, name: &str) -> Option<T>
+    where
+        T::Err: Display,
+    {
+        self.get_opt(name).map(|value| parse_value(name, value))
+    }
+}
+
+fn parse_value<T: FromStr>(name: &str, value: &str) -> T
+where
+    T::Err: Display,
+{
+    value.parse().unwrap_or_else(|err| fail(format!("invalid value '{value}' for '--{name}': {err}")))
+}
+
+/// Prints an error and exits with a non-zero code.
+pub fn fail(msg: impl Display) -> ! {
+    eprintln!("error: {msg}");
+    std::process::exit(1);
+}
+
+pub fn open_input(path: &str) -> BufReader<File> {
+    if !Path::new(path).is_file() {
+        fail(format!("input file '{path}' does not exist"));
+    }
+
+    BufReader::new(File::open(path).unwrap_or_else(|err| fail(format!("could not open '{path}': {err}"))))
+}
+
+pub fn create_output(path: &str, inputs: &[&str]) -> BufWriter<File> {
+    let out = Path::new(path);
+
+    if let Some(parent) = out.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        if !parent.is_dir() {
+            fail(format!("output directory '{}' does not exist", parent.display()));
+        }
+    }
+
+    for input in inputs {
+        if same_file(path, input) {
+            fail(format!("output '{path}' would overwrite input '{input}'"));
+        }
+    }
+
+    BufWriter::new(File::create(path).unwrap_or_else(|err| fail(format!("could not create '{path}': {err}"))))
+}
+
+fn same_file(a: &str, b: &str) -> bool {
+    match (Path::new(a).canonicalize(), Path::new(b).canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cli() -> Cli {
+        Cli::new("test", "Test binary")
+            .required("input", "Input")
+            .default("threads", "4", "Threads")
+            .optional("seed", "Seed")
+            .multiple("filter", "Filter")
+            .flag("verbose", "Verbose")
+    }
+
+    fn parse(args: &[&str]) -> Result<Matches, Option<String>> {
+        cli().try_parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn values_and_defaults() {
+        let matches = parse(&["--input", "a.binpack", "--filter=x", "--filter", "y", "--verbose"]).unwrap();
+
+        assert_eq!(matches.get("input"), "a.binpack");
+        assert_eq!(matches.get("threads"), "4");
+        assert_eq!(matches.parse::<usize>("threads"), 4);
+        assert_eq!(matches.get_opt("seed"), None);
+        assert_eq!(matches.get_all("filter"), ["x", "y"]);
+        assert!(matches.flag("verbose"));
+
+        let matches = parse(&["--threads=8", "--input=b.binpack"]).unwrap();
+        assert_eq!(matches.parse::<usize>("threads"), 8);
+        assert!(matches.get_all("filter").is_empty());
+        assert!(!matches.flag("verbose"));
+    }
+
+    #[test]
+    fn missing() {
+        assert_eq!(parse(&[]).err(), Some(Some("missing required option '--input'".to_string())));
+        assert_eq!(parse(&["--input"]).err(), Some(Some("missing value for '--input'".to_string())));
+    }
+
+    #[test]
+    fn unknown_and_malformed() {
+        let err = |args: &[&str]| parse(args).err().flatten().unwrap();
+
+        assert_eq!(err(&["--input", "a", "--output", "b"]), "unknown option '--output'");
+        assert_eq!(err(&["a.binpack"]), "unexpected argument 'a.binpack'");
+        assert_eq!(err(&["--input", "a", "--verbose=yes"]), "'--verbose' does not take a value");
+        assert_eq!(err(&["--input", "a", "--input", "b"]), "'--input' given more than once");
+    }
+
+    #[test]
+    fn help() {
+        assert_eq!(parse(&["--help"]).err(), Some(None));
+        assert_eq!(parse(&["--input", "a", "-h"]).err(), Some(None));
+
+        let usage = cli().usage();
+        assert!(usage.starts_with("Test binary\n\nUsage: test --input <input> [--filter <filter>]... [options]"));
+        assert!(usage.contains("[default: 4]"));
+    }
+}
@@ -0,0 +1,412 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+};
+
+use crate::{
+    cli::{self, Cli},
+    files,
+    format::{self, GameFormat},
+    progress::Progress,
+    rng,
+};
+
+#[derive(Clone, Copy, Default)]
+struct Counts {
+    games: u64,
+    games_within: u64,
+    games_across: u64,
+    positions: u64,
+    positions_within: u64,
+    positions_across: u64,
+}
+
+impl Counts {
+    fn add(&mut self, other: &Self) {
+        self.games += other.games;
+        self.games_within += other.games_within;
+        self.games_across += other.games_across;
+        self.positions += other.positions;
+        self.positions_within += other.positions_within;
+        self.positions_across += other.positions_across;
+    }
+
+    fn record_game(&mut self, same_file: bool) {
+        if same_file {
+            self.games_within += 1;
+        } else {
+            self.games_across += 1;
+        }
+    }
+
+    fn record_position(&mut self, same_file: bool) {
+        if same_file {
+            self.positions_within += 1;
+        } else {
+            self.positions_across += 1;
+        }
+    }
+
+    fn print(&self, positions: bool) {
+        let pct = |n: u64, total: u64| n as f64 / total.max(1) as f64 * 100.0;
+
+        let (games, within, across) = (self.games, self.games_within, self.games_across);
+        println!(" - Games     : {games}");
+        println!("   - Duplicated within file : {within} ({:.2}%)", pct(within, games));
+        println!("   - Duplicated across files: {across} ({:.2}%)", pct(across, games));
+
+        if positions {
+            let (positions, within, across) = (self.positions, self.positions_within, self.positions_across);
+            println!(" - Positions : {positions}");
+            println!("   - Duplicated within file : {within} ({:.2}%)", pct(within, positions));
+            println!("   - Duplicated across files: {across} ({:.2}%)", pct(across, positions));
+        }
+    }
+}
+
+/// What has been written so far, when an output is given.
+#[derive(Default)]
+struct Output {
+    games: u64,
+    dropped_plies: u64,
+    split_games: u64,
+    dropped_games: u64,
+    /// Times each position has been written, only tracked with a cap.
+    positions: HashMap<u64, u32>,
+}
+
+/// Games and positions seen across every file read so far, and where the
+/// games that are not duplicates go.
+struct Dedup<W> {
+    positions: bool,
+    cap: Option<u32>,
+    /// First file each game was seen in.
+    seen_games: HashMap<u64, u32>,
+    /// First file each position was seen in.
+    seen_positions: HashMap<u64, u32>,
+    writer: Option<(W, Output)>,
+}
+
+impl<W: Write> Dedup<W> {
+    fn new(positions: bool, cap: Option<u32>, writer: Option<W>) -> Self {
+        Self {
+            positions: positions || cap.is_some(),
+            cap,
+            seen_games: HashMap::new(),
+            seen_positions: HashMap::new(),
+            writer: writer.map(|writer| (writer, Output::default())),
+        }
+    }
+
+    /// Counts the duplicates in a game read from the `file`th input, then
+    /// writes it unless the whole game was seen before.
+    fn add<T: GameFormat>(&mut self, file: u32, bytes: &[u8], counts: &mut Counts) -> io::Result<()> {
+        counts.games += 1;
+
+        let hash = rng::hash_bytes(bytes);
+        let duplicate = match self.seen_games.get(&hash) {
+            Some(&first) => {
+                counts.record_game(first == file);
+                true
+            }
+            None => {
+                self.seen_games.insert(hash, file);
+                false
+            }
+        };
+
+        if !self.positions && duplicate {
+            return Ok(());
+        }
+
+        let keys = if self.positions { game_keys(&T::decode(bytes)?) } else { Vec::new() };
+
+        for &key in &keys {
+            counts.positions += 1;
+
+            match self.seen_positions.get(&key) {
+                Some(&first) => counts.record_position(first == file),
+                None => {
+                    self.seen_positions.insert(key, file);
+                }
+            }
+        }
+
+        match self.writer.as_mut() {
+            Some((writer, output)) if !duplicate => write_game::<T>(writer, output, bytes, &keys, self.cap),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Entry point for the `dedup` binaries of each format.
+pub fn main<T: GameFormat>(name: &'static str, about: &'static str) {
+    let args = Cli::new(name, about)
+        .multiple("input", "Binpack, folder or glob to read from")
+        .optional("output", "Binpack to write deduplicated games to, only reports if not given")
+        .flag("recursive", "Also read .binpack files in subfolders of folder inputs")
+        .flag("positions", "Also count duplicated positions, by Zobrist key")
+        .optional(
+            "max-position-count",
+            "Drop plies of written games whose position was already written this many times, \
+             splitting the game around them",
+        )
+        .parse();
+
+    let inputs = files::expand_inputs(args.get_all("input"), args.flag("recursive"), "binpack");
+    let cap = args.parse_opt::<u32>("max-position-count");
+
+    if cap == Some(0) {
+        cli::fail("--max-position-count must be at least 1");
+    }
+
+    let input_refs = inputs.iter().map(String::as_str).collect::<Vec<_>>();
+    let writer = args.get_opt("output").map(|path| cli::create_output(path, &input_refs));
+    let mut dedup = Dedup::new(args.flag("positions"), cap, writer);
+
+    let mut total = Counts::default();
+    let mut buffer = Vec::new();
+
+    println!("Format: {}", T::NAME);
+
+    for (file, path) in inputs.iter().enumerate() {
+        let mut reader = cli::open_input(path);
+        let size = reader.get_ref().metadata().map(|meta| meta.len()).unwrap_or(0);
+
+        let mut counts = Counts::default();
+        let mut read = 0;
+        let mut progress = Progress::fraction("Read", "Bytes", size, 1024 * 1024 * 64);
+
+        let result = (|| -> io::Result<()> {
+            while !reader.fill_buf()?.is_empty() {
+                T::deserialise_fast_into_buffer(&mut reader, &mut buffer)?;
+                read += buffer.len() as u64;
+                progress.update(read);
+
+                dedup.add::<T>(file as u32, &buffer, &mut counts)?;
+            }
+
+            Ok(())
+        })();
+
+        progress.finish();
+
+        if let Err(err) = result {
+            cli::fail(format!("could not read '{path}': {err}"));
+        }
+
+        println!("{path}:");
+        counts.print(dedup.positions);
+
+        if counts.games > 0 && counts.games_across == counts.games {
+            println!(" - Every game already appears in an earlier file!");
+        }
+
+        total.add(&counts);
+    }
+
+    println!("Total:");
+    total.print(dedup.positions);
+
+    if let Some((mut writer, output)) = dedup.writer {
+        if let Err(err) = writer.flush() {
+            cli::fail(format!("could not write output: {err}"));
+        }
+
+        println!("Written {} Games", output.games);
+        if cap.is_some() {
+            println!(" - Dropped Plies: {}", output.dropped_plies);
+            println!(" - Split Games  : {}", output.split_games);
+            println!(" - Dropped Games: {}", output.dropped_games);
+        }
+    }
+}
+
+/// Keys of the positions each move of the game is played from.
+fn game_keys<T: GameFormat>(game: &T) -> Vec<u64> {
+    let mut pos = game.startpos();
+    let castling = game.castling();
+
+    game.plies()
+        .iter()
+        .map(|ply| {
+            let key = format::position_key(&pos);
+            pos.make(ply.best_move, &castling);
+            key
+        })
+        .collect()
+}
+
+/// Writes a game, dropping every ply whose position has hit the cap. The
+/// plies either side of a dropped one no longer follow on from each other, so
+/// each run of kept plies is written as a game of its own.
+fn write_game<T: GameFormat>(
+    writer: &mut impl Write,
+    output: &mut Output,
+    bytes: &[u8],
+    keys: &[u64],
+    cap: Option<u32>,
+) -> io::Result<()> {
+    let Some(cap) = cap else {
+        output.games += 1;
+        return writer.write_all(bytes);
+    };
+
+    let mut runs = Vec::new();
+    let mut start = None;
+
+    for (ply, key) in keys.iter().enumerate() {
+        let count = output.positions.entry(*key).or_default();
+        let keep = *count < cap;
+
+        if keep {
+            *count += 1;
+        } else {
+            output.dropped_plies += 1;
+        }
+
+        match (keep, start) {
+            (true, None) => start = Some(ply),
+            (false, Some(from)) => {
+                runs.push(from..ply);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    runs.extend(start.map(|from| from..keys.len()));
+
+    match runs.as_slice() {
+        [] => {
+            output.dropped_games += 1;
+            Ok(())
+        }
+        [run] if run.len() == keys.len() => {
+            output.games += 1;
+            writer.write_all(bytes)
+        }
+        _ => {
+            if runs.len() > 1 {
+                output.split_games += 1;
+            }
+
+            for run in runs {
+                let mut game = T::decode(bytes)?;
+                game.keep_plies(run);
+                game.encode(writer)?;
+                output.games += 1;
+            }
+
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use montyformat::MontyValueFormat;
+
+    use super::*;
+    use crate::testing::{self, STARTPOS};
+
+    fn game(moves: &[&str]) -> MontyValueFormat {
+        testing::value_game(STARTPOS, moves, 0.5)
+    }
+
+    /// Runs each file of games through a `Dedup`, returning the counts of
+    /// each file and the games written.
+    fn dedup(files: &[&[MontyValueFormat]], positions: bool, cap: Option<u32>) -> (Vec<Counts>, Vec<u8>, Output) {
+        let mut dedup = Dedup::new(positions, cap, Some(Vec::new()));
+        let mut counts = Vec::new();
+
+        for (file, games) in files.iter().enumerate() {
+            let mut file_counts = Counts::default();
+
+            for game in *games {
+                let bytes = testing::binpack(std::slice::from_ref(game));
+                dedup.add::<MontyValueFormat>(file as u32, &bytes, &mut file_counts).unwrap();
+            }
+
+            counts.push(file_counts);
+        }
+
+        let (written, output) = dedup.writer.unwrap();
+        (counts, written, output)
+    }
+
+    fn moves(game: &MontyValueFormat) -> Vec<String> {
+        game.moves.iter().map(|data| data.best_move.to_uci(&game.castling)).collect()
+    }
+
+    #[test]
+    fn counts_and_drops_duplicate_games() {
+        let files: [&[MontyValueFormat]; 2] =
+            [&[game(&["e4", "e5"]), game(&["d4", "d5"]), game(&["e4", "e5"])], &[game(&["d4", "d5"]), game(&["c4"])]];
+
+        let (counts, written, output) = dedup(&files, false, None);
+
+        assert_eq!((counts[0].games, counts[0].games_within, counts[0].games_across), (3, 1, 0));
+        assert_eq!((counts[1].games, counts[1].games_within, counts[1].games_across), (2, 0, 1));
+        assert_eq!(counts[0].positions, 0);
+
+        assert_eq!(output.games, 3);
+        let written = testing::read_value_games(&written).iter().map(moves).collect::<Vec<_>>();
+        assert_eq!(written, [vec!["e2e4", "e7e5"], vec!["d2d4", "d7d5"], vec!["c2c4"]]);
+    }
+
+    #[test]
+    fn counts_duplicate_positions() {
+        let files: [&[MontyValueFormat]; 2] =
+            [&[game(&["Nf3", "Nf6", "Nc3", "e5"]), game(&["d4"])], &[game(&["Nc3", "Nf6", "Nf3", "e5"])]];
+
+        let (counts, _, output) = dedup(&files, true, None);
+
+        // the second game starts from the same position as the first
+        assert_eq!(counts[0].positions, 5);
+        assert_eq!((counts[0].positions_within, counts[0].positions_across), (1, 0));
+
+        // the start position again, and 3... e5 by transposition
+        assert_eq!(counts[1].positions, 4);
+        assert_eq!((counts[1].positions_within, counts[1].positions_across), (0, 2));
+
+        assert_eq!(counts[1].games_across, 0);
+        assert_eq!(output.games, 3);
+    }
+
+    #[test]
+    fn cap_drops_plies_anywhere_in_the_game() {
+        // returns to the start position before 3. e4
+        let first = game(&["Nf3", "Nf6", "Ng1", "Ng8", "e4"]);
+        // repeats the position after 2. Ng1 of the first game by transposition,
+        // then returns to the start position before 3. d4
+        let second = game(&["Nc3", "Nf6", "Nb1", "Ng8", "d4", "d5"]);
+        let second_keys = game_keys(&second);
+
+        let (_, written, output) = dedup(&[&[first, second, game(&["e4"])]], true, Some(1));
+        let written = testing::read_value_games(&written);
+
+        assert_eq!(written.len(), 3);
+        assert_eq!(moves(&written[0]), ["g1f3", "g8f6", "f3g1", "f6g8"]);
+
+        // the second game is split around its repeated plies
+        assert_eq!(moves(&written[1]), ["g8f6", "c3b1"]);
+        assert_eq!(format::position_key(&written[1].startpos), second_keys[1]);
+        assert_eq!(written[1].moves[0].score, 10);
+
+        assert_eq!(moves(&written[2]), ["d7d5"]);
+        assert_eq!(format::position_key(&written[2].startpos), second_keys[5]);
+        assert_eq!(written[2].moves[0].score, 50);
+
+        assert_eq!((output.games, output.dropped_plies, output.split_games, output.dropped_games), (3, 5, 1, 1));
+    }
+
+    #[test]
+    fn games_under_the_cap_are_written_unchanged() {
+        let games = [game(&["e4", "e5"]), game(&["e4", "c5"])];
+        let (_, written, output) = dedup(&[&games], true, Some(2));
+
+        assert_eq!(written, testing::binpack(&games));
+        assert_eq!((output.games, output.dropped_plies), (2, 0));
+    }
+}
@@ -0,0 +1,78 @@
+/// Opcode for the white-relative game result, `"1-0"`, `"0-1"` or `"1/2-1/2"`.
+pub const RESULT: &str = "c9";
+/// Opcode for the visit distribution, as `"<uci>:<visits> ..."`.
+pub const VISITS: &str = "c1";
+
+/// A line of an EPD file: the four position fields and the `;`-terminated
+/// operations after them.
+pub struct Epd {
+    pub fen: String,
+    pub opcodes: Vec<(String, String)>,
+}
+
+impl Epd {
+    /// Returns `None` for lines with fewer than four fields. The halfmove and
+    /// fullmove counters are taken from `hmvc` and `fmvn` when present.
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.split_whitespace();
+        let position = fields.by_ref().take(4).collect::<Vec<_>>();
+
+        if position.len() < 4 {
+            return None;
+        }
+
+        let rest = fields.collect::<Vec<_>>().join(" ");
+
+        let mut opcodes = Vec::new();
+        for op in rest.split(';') {
+            let op = op.trim();
+            let (code, operand) = op.split_once(' ').unwrap_or((op, ""));
+
+            if !code.is_empty() {
+                opcodes.push((code.to_string(), operand.trim().trim_matches('"').to_string()));
+            }
+        }
+
+        let mut epd = Self { fen: String::new(), opcodes };
+        let halfmove = epd.get("hmvc").unwrap_or("0");
+        let fullmove = epd.get("fmvn").unwrap_or("1");
+        epd.fen = format!("{} {halfmove} {fullmove}", position.join(" "));
+
+        Some(epd)
+    }
+
+    pub fn get(&self, code: &str) -> Option<&str> {
+        self.opcodes.iter().find(|(key, _)| key == code).map(|(_, operand)| operand.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_opcodes_and_counters() {
+        let epd = Epd::parse("r3k3/8/8/8/8/8/8/4K2R w Kq - hmvc 3; fmvn 20; bm O-O; c9 \"1-0\"; c1 \"e1g1:3 h1h8:1\";")
+            .unwrap();
+
+        assert_eq!(epd.fen, "r3k3/8/8/8/8/8/8/4K2R w Kq - 3 20");
+        assert_eq!(epd.get("bm"), Some("O-O"));
+        assert_eq!(epd.get(RESULT), Some("1-0"));
+        assert_eq!(epd.get(VISITS), Some("e1g1:3 h1h8:1"));
+        assert_eq!(epd.get("ce"), None);
+    }
+
+    #[test]
+    fn defaults_counters_and_allows_no_opcodes() {
+        let epd = Epd::parse("8/8/8/8/8/8/8/K6k b - -").unwrap();
+
+        assert_eq!(epd.fen, "8/8/8/8/8/8/8/K6k b - - 0 1");
+        assert!(epd.opcodes.is_empty());
+    }
+
+    #[test]
+    fn rejects_short_lines() {
+        assert!(Epd::parse("8/8/8/8/8/8/8/K6k b -").is_none());
+        assert!(Epd::parse("").is_none());
+    }
+}
@@ -0,0 +1,257 @@
+use std::{
+    io::{self, BufRead, Write},
+    ops::Range,
+};
+
+use montyformat::chess::{Castling, Move, Position};
+
+use crate::{
+    cli::{self, Cli},
+    epd, files,
+    format::{GameFormat, Ply},
+    pgn, san,
+};
+
+/// How each position is written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Style {
+    /// `<fen> | <score> | <result> | <best move>`, followed by
+    /// ` | <move>:<visits> ...` when the format has visit distributions. The
+    /// score is from the side to move's perspective, as stored, and the result
+    /// is white-relative.
+    Text,
+    /// An EPD line with `hmvc` and `fmvn` for the counters, `ce` for the side
+    /// to move's centipawns, `bm` for the best move in SAN, then
+    /// [`epd::RESULT`] and [`epd::VISITS`], if the format has them.
+    Epd,
+}
+
+/// Entry point for the `export` binaries of each format. Each position a
+/// move is played from becomes one line in the chosen [`Style`].
+pub fn main<T: GameFormat>(name: &'static str, about: &'static str) {
+    let args = Cli::new(name, about)
+        .required("input", "Binpack to read")
+        .optional("output", "Text file to write, stdout if not given")
+        .default("format", "text", "Line format, 'text' or 'epd'")
+        .optional("games", "Only export games in this range of indices, e.g. '100..200', '..200' or '100..'")
+        .optional("fen", "Only export positions whose FEN matches this pattern, with '*' and '?' wildcards")
+        .parse();
+
+    let inp_path = args.get("input");
+    let games = args.get_opt("games").map_or(Ok(0..u64::MAX), parse_range).unwrap_or_else(|err| cli::fail(err));
+    let pattern = args.get_opt("fen");
+
+    let style = match args.get("format") {
+        "text" => Style::Text,
+        "epd" => Style::Epd,
+        other => cli::fail(format!("unknown format '{other}', expected 'text' or 'epd'")),
+    };
+
+    let reader = cli::open_input(inp_path);
+
+    let result = match args.get_opt("output") {
+        Some(out_path) => {
+            let mut writer = cli::create_output(out_path, &[inp_path]);
+            let positions =
+                export::<T>(reader, &mut writer, style, games, pattern).and_then(|n| writer.flush().map(|_| n));
+            positions.map(|positions| println!("Exported {positions} Positions"))
+        }
+        None => export::<T>(reader, &mut io::stdout().lock(), style, games, pattern).map(|_| ()),
+    };
+
+    // stdout going away, e.g. piping into `head`, is not an error
+    if let Err(err) = result.or_else(|err| if err.kind() == io::ErrorKind::BrokenPipe { Ok(()) } else { Err(err) }) {
+        cli::fail(format!("export failed: {err}"));
+    }
+}
+
+/// Parses `start..end`, `start..`, `..end` or a single index.
+pub fn parse_range(arg: &str) -> Result<Range<u64>, String> {
+    let parse = |s: &str| s.parse::<u64>().map_err(|err| format!("invalid range '{arg}': {err}"));
+
+    match arg.split_once("..") {
+        Some(("", "")) => Ok(0..u64::MAX),
+        Some(("", end)) => Ok(0..parse(end)?),
+        Some((start, "")) => Ok(parse(start)?..u64::MAX),
+        Some((start, end)) => Ok(parse(start)?..parse(end)?),
+        None => parse(arg).map(|index| index..index + 1),
+    }
+}
+
+/// Writes the selected positions, returning how many were written.
+pub fn export<T: GameFormat>(
+    mut reader: impl BufRead,
+    writer: &mut impl Write,
+    style: Style,
+    games: Range<u64>,
+    pattern: Option<&str>,
+) -> io::Result<u64> {
+    let mut buffer = Vec::new();
+    let mut positions = 0;
+
+    for index in 0..games.end {
+        if reader.fill_buf()?.is_empty() {
+            break;
+        }
+
+        T::deserialise_fast_into_buffer(&mut reader, &mut buffer)?;
+
+        if index < games.start {
+            continue;
+        }
+
+        let game = T::decode(&buffer)?;
+        let castling = game.castling();
+        let result = game.result();
+        let mut pos = game.startpos();
+
+        for ply in game.plies() {
+            let fen = pos.as_fen();
+
+            if pattern.is_none_or(|pattern| files::wildcard_match(pattern, &fen)) {
+                match style {
+                    Style::Text => write_text(writer, &fen, &castling, result, &ply)?,
+                    Style::Epd => write_epd::<T>(writer, &pos, &fen, &castling, result, &ply)?,
+                }
+
+                positions += 1;
+            }
+
+            pos.make(ply.best_move, &castling);
+        }
+    }
+
+    Ok(positions)
+}
+
+fn write_text(writer: &mut impl Write, fen: &str, castling: &Castling, result: f32, ply: &Ply) -> io::Result<()> {
+    let uci = |mov: Move| mov.to_uci(castling);
+
+    write!(writer, "{fen} | {} | {result:.1} | {}", ply.score, uci(ply.best_move))?;
+
+    if let Some(visits) = ply.visits {
+        write!(writer, " |")?;
+        for &(mov, count) in visits {
+            write!(writer, " {}:{count}", uci(mov))?;
+        }
+    }
+
+    writeln!(writer)
+}
+
+fn write_epd<T: GameFormat>(
+    writer: &mut impl Write,
+    pos: &Position,
+    fen: &str,
+    castling: &Castling,
+    result: f32,
+    ply: &Ply,
+) -> io::Result<()> {
+    let fields = fen.split_whitespace().collect::<Vec<_>>();
+    let (position, halfmove, fullmove) = (fields[..4].join(" "), fields[4], fields[5]);
+    let centipawns = T::centipawns(ply.score).round() as i32;
+    let san = |mov: Move| san::to_san(pos, castling, mov);
+
+    write!(writer, "{position} hmvc {halfmove}; fmvn {fullmove}; ce {centipawns}; bm {};", san(ply.best_move))?;
+    write!(writer, " {} \"{}\";", epd::RESULT, pgn::result_str(result))?;
+
+    if let Some(visits) = ply.visits {
+        let visits = visits.iter().map(|&(mov, count)| format!("{}:{count}", mov.to_uci(castling))).collect::<Vec<_>>();
+        write!(writer, " {} \"{}\";", epd::VISITS, visits.join(" "))?;
+    }
+
+    writeln!(writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use montyformat::MontyFormat;
+
+    use super::*;
+    use crate::testing::{self, STARTPOS};
+
+    fn run<T: GameFormat>(games: &[T], style: Style, range: Range<u64>, pattern: Option<&str>) -> Vec<String> {
+        let bytes = testing::binpack(games);
+        let mut output = Vec::new();
+        let positions = export::<T>(&bytes[..], &mut output, style, range, pattern).unwrap();
+
+        let lines = String::from_utf8(output).unwrap().lines().map(str::to_string).collect::<Vec<_>>();
+        assert_eq!(positions, lines.len() as u64);
+        lines
+    }
+
+    #[test]
+    fn parses_ranges() {
+        assert_eq!(parse_range("100..200"), Ok(100..200));
+        assert_eq!(parse_range("100.."), Ok(100..u64::MAX));
+        assert_eq!(parse_range("..200"), Ok(0..200));
+        assert_eq!(parse_range(".."), Ok(0..u64::MAX));
+        assert_eq!(parse_range("7"), Ok(7..8));
+        assert!(parse_range("a..2").is_err());
+        assert!(parse_range("1..-2").is_err());
+    }
+
+    #[test]
+    fn writes_text_lines() {
+        let games = [testing::value_game(STARTPOS, &["e4", "c5"], 1.0)];
+
+        assert_eq!(
+            run(&games, Style::Text, 0..u64::MAX, None),
+            [
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w kqKQ - 0 1 | 0 | 1.0 | e2e4",
+                "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b kqKQ - 0 1 | 10 | 1.0 | c7c5",
+            ]
+        );
+    }
+
+    #[test]
+    fn writes_epd_lines() {
+        let games = [testing::value_game("r3k3/8/8/8/8/8/8/4K2R w Kq - 0 1", &["O-O", "O-O-O"], 0.5)];
+
+        assert_eq!(
+            run(&games, Style::Epd, 0..u64::MAX, None),
+            [
+                "r3k3/8/8/8/8/8/8/4K2R w qK - hmvc 0; fmvn 1; ce 0; bm O-O; c9 \"1/2-1/2\";",
+                "r3k3/8/8/8/8/8/8/5RK1 b q - hmvc 1; fmvn 1; ce 10; bm O-O-O; c9 \"1/2-1/2\";",
+            ]
+        );
+    }
+
+    #[test]
+    fn writes_visits_in_uci() {
+        let games = [testing::policy_game("7k/8/6K1/8/8/8/8/R7 w - - 0 1", &["Ra8#"], 1.0)];
+        let lines = run(&games, Style::Epd, 0..u64::MAX, None);
+
+        let epd = epd::Epd::parse(&lines[0]).unwrap();
+        assert_eq!(epd.get("bm"), Some("Ra8#"));
+        assert_eq!(epd.get(epd::RESULT), Some("1-0"));
+
+        let visits = epd.get(epd::VISITS).unwrap().split(' ').collect::<Vec<_>>();
+        assert!(visits.contains(&"a1a8:255"), "{visits:?}");
+        assert!(visits.contains(&"g6f7:0"), "{visits:?}");
+        assert_eq!(visits.len(), 20);
+
+        let text = run(&games, Style::Text, 0..u64::MAX, None);
+        let (_, text_visits) = text[0].rsplit_once(" | ").unwrap();
+        assert_eq!(text_visits, epd.get(epd::VISITS).unwrap());
+    }
+
+    #[test]
+    fn selects_games_and_positions() {
+        let games = [
+            testing::value_game(STARTPOS, &["e4", "e5"], 1.0),
+            testing::value_game(STARTPOS, &["d4", "d5"], 0.0),
+            testing::value_game(STARTPOS, &["c4", "c5"], 0.5),
+        ];
+
+        let best_moves = |lines: Vec<String>| {
+            lines.iter().map(|line| line.rsplit(' ').next().unwrap().to_string()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(best_moves(run(&games, Style::Text, 1..2, None)), ["d2d4", "d7d5"]);
+        assert_eq!(best_moves(run(&games, Style::Text, 2..u64::MAX, None)), ["c2c4", "c7c5"]);
+        assert_eq!(best_moves(run(&games, Style::Text, 0..u64::MAX, Some("* b *"))), ["e7e5", "d7d5", "c7c5"]);
+        assert_eq!(best_moves(run(&games, Style::Text, 0..2, Some("*/3P4/*"))), ["d7d5"]);
+        assert!(run::<MontyFormat>(&[], Style::Text, 0..u64::MAX, None).is_empty());
+    }
+}
@@ -0,0 +1,140 @@
+use montyformat::chess::{Castling, Position, Right};
+
+use crate::{san, verify};
+
+/// Parses a FEN from user input, which `Position::parse_fen` would panic on
+/// if malformed. The text is checked first and the position after.
+pub fn parse(fen: &str) -> Result<(Position, Castling), String> {
+    check(fen)?;
+
+    let mut castling = Castling::default();
+    let pos = Position::parse_fen(fen, &mut castling);
+    verify::check_position(&pos)?;
+
+    Ok((pos, castling))
+}
+
+/// `Position::as_fen` with castling rights in the usual `KQkq` order, rather
+/// than black's first, and the en passant square filled in.
+pub fn write(pos: &Position) -> String {
+    let mut fields = pos.as_fen().split_whitespace().map(str::to_string).collect::<Vec<_>>();
+
+    if pos.rights() != 0 {
+        fields[2] = [(Right::WKS, 'K'), (Right::WQS, 'Q'), (Right::BKS, 'k'), (Right::BQS, 'q')]
+            .iter()
+            .filter(|&&(bit, _)| pos.rights() & bit > 0)
+            .map(|&(_, right)| right)
+            .collect();
+    }
+
+    if pos.enp_sq() > 0 {
+        fields[3] = san::square_name(usize::from(pos.enp_sq()));
+    }
+
+    fields.join(" ")
+}
+
+/// Checks the six fields of a FEN, and that each side has one king.
+pub fn check(fen: &str) -> Result<(), String> {
+    let fields = fen.split_whitespace().collect::<Vec<_>>();
+
+    let [board, stm, rights, enp, halfmove, fullmove] = fields[..] else {
+        return Err(format!("expected 6 fields, found {}", fields.len()));
+    };
+
+    let ranks = board.split('/').collect::<Vec<_>>();
+    if ranks.len() != 8 {
+        return Err(format!("expected 8 ranks, found {}", ranks.len()));
+    }
+
+    for (i, rank) in ranks.iter().enumerate() {
+        let mut files = 0;
+
+        for c in rank.chars() {
+            files += match c {
+                '1'..='8' => c.to_digit(10).unwrap(),
+                'p' | 'n' | 'b' | 'r' | 'q' | 'k' | 'P' | 'N' | 'B' | 'R' | 'Q' | 'K' => 1,
+                _ => return Err(format!("invalid character '{c}' on rank {}", 8 - i)),
+            };
+        }
+
+        if files != 8 {
+            return Err(format!("rank {} has {files} squares", 8 - i));
+        }
+    }
+
+    for king in ['K', 'k'] {
+        let count = board.chars().filter(|&c| c == king).count();
+        if count != 1 {
+            return Err(format!("expected one '{king}', found {count}"));
+        }
+    }
+
+    if stm != "w" && stm != "b" {
+        return Err(format!("invalid side to move '{stm}'"));
+    }
+
+    let valid_right = |c: char| "KQkq".contains(c) || ('A'..='H').contains(&c) || ('a'..='h').contains(&c);
+    if rights != "-" && (!rights.chars().all(valid_right) || rights.len() > 4) {
+        return Err(format!("invalid castling rights '{rights}'"));
+    }
+
+    let valid_enp = |sq: &[u8]| matches!(sq, [b'a'..=b'h', b'3' | b'6']);
+    if enp != "-" && !valid_enp(enp.as_bytes()) {
+        return Err(format!("invalid en passant square '{enp}'"));
+    }
+
+    if halfmove.parse::<u32>().is_err() || fullmove.parse::<u32>().is_err() {
+        return Err(format!("invalid move counters '{halfmove} {fullmove}'"));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_valid_fens() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/8/8/8/4Pp2/8/8/R3K2R b Qk e3 0 23",
+            "bqnrkrnb/pppppppp/8/8/8/8/PPPPPPPP/BQNRKRNB w FDfd - 0 1",
+            "8/8/8/8/8/8/8/K6k w - - 99 150",
+        ] {
+            assert_eq!(check(fen), Ok(()), "{fen}");
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_fens() {
+        for (fen, err) in [
+            ("", "expected 6 fields, found 0"),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -", "expected 6 fields, found 4"),
+            ("rnbqkbnr/pppppppp/8/8/8/8/RNBQKBNR w KQkq - 0 1", "expected 8 ranks, found 7"),
+            ("rnbqkbnr/pppppppp/45/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "rank 6 has 9 squares"),
+            ("rnbqkbnr/ppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "rank 7 has 7 squares"),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQXBNR w KQkq - 0 1", "invalid character 'X' on rank 1"),
+            ("rnbqqbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQ - 0 1", "expected one 'k', found 0"),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBKKBNR w kq - 0 1", "expected one 'K', found 2"),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1", "invalid side to move 'x'"),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkqX - 0 1", "invalid castling rights 'KQkqX'"),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e4 0 1", "invalid en passant square 'e4'"),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - x 1", "invalid move counters 'x 1'"),
+        ] {
+            assert_eq!(check(fen), Err(err.to_string()), "{fen}");
+        }
+    }
+
+    #[test]
+    fn writes_fens_as_parsed() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/8/8/8/4Pp2/8/8/R3K2R b Qk e3 0 23",
+            "8/8/8/8/8/8/8/K6k w - - 99 150",
+        ] {
+            assert_eq!(write(&parse(fen).unwrap().0), fen);
+        }
+    }
+}
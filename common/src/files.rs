@@ -0,0 +1,213 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::cli;
+
+/// Expands each argument into the files it names, exiting if nothing matches:
+/// - a file is used as-is
+/// - a folder contributes its files with the given extension, and those of
+///   every subfolder if `recursive` is set
+/// - anything containing `*` or `?` is treated as a glob, where `**` matches
+///   any number of folders
+pub fn expand_inputs(args: &[String], recursive: bool, extension: &str) -> Vec<String> {
+    let mut inputs = Vec::new();
+
+    for arg in args {
+        let path = Path::new(arg);
+
+        let found = if is_glob(arg) {
+            glob(arg)
+        } else if path.is_dir() {
+            with_extension(path, recursive, extension)
+        } else if path.is_file() {
+            Ok(vec![path.to_path_buf()])
+        } else {
+            cli::fail(format!("input '{arg}' does not exist"));
+        };
+
+        let found = found.unwrap_or_else(|err| cli::fail(format!("could not read '{arg}': {err}")));
+
+        if found.is_empty() {
+            cli::fail(format!("no files found for input '{arg}'"));
+        }
+
+        inputs.extend(found.iter().map(|path| path.to_string_lossy().into_owned()));
+    }
+
+    inputs
+}
+
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?'])
+}
+
+/// Files in `dir` with the given extension, sorted.
+pub fn with_extension(dir: &Path, recursive: bool, extension: &str) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    walk(dir, recursive, &mut |path| {
+        if path.extension().and_then(|ext| ext.to_str()) == Some(extension) {
+            files.push(path.to_path_buf());
+        }
+    })?;
+
+    files.sort();
+    Ok(files)
+}
+
+fn walk(dir: &Path, recursive: bool, f: &mut impl FnMut(&Path)) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_file() {
+            f(&path);
+        } else if recursive && path.is_dir() {
+            walk(&path, recursive, f)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// All files matching `pattern`, sorted.
+pub fn glob(pattern: &str) -> io::Result<Vec<PathBuf>> {
+    let mut base = PathBuf::new();
+    let mut parts = Vec::new();
+
+    for comp in Path::new(pattern).components() {
+        let comp_str = comp.as_os_str().to_string_lossy();
+
+        if parts.is_empty() && !is_glob(&comp_str) {
+            base.push(comp);
+        } else {
+            parts.push(comp_str.into_owned());
+        }
+    }
+
+    let relative = base.as_os_str().is_empty();
+    if relative {
+        base.push(".");
+    }
+
+    let mut matches = Vec::new();
+    glob_from(&base, &parts, &mut matches)?;
+
+    if relative {
+        for path in &mut matches {
+            if let Ok(stripped) = path.strip_prefix(".") {
+                *path = stripped.to_path_buf();
+            }
+        }
+    }
+
+    matches.sort();
+    matches.dedup();
+    Ok(matches)
+}
+
+fn glob_from(path: &Path, parts: &[String], matches: &mut Vec<PathBuf>) -> io::Result<()> {
+    let Some((part, rest)) = parts.split_first() else {
+        if path.is_file() {
+            matches.push(path.to_path_buf());
+        }
+
+        return Ok(());
+    };
+
+    if !path.is_dir() {
+        return Ok(());
+    }
+
+    if part == "**" {
+        glob_from(path, rest, matches)?;
+    }
+
+    for entry in fs::read_dir(path)? {
+        let entry = entry?.path();
+        let name = entry.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+
+        if part == "**" {
+            if entry.is_dir() {
+                glob_from(&entry, parts, matches)?;
+            }
+        } else if wildcard_match(part, &name) {
+            glob_from(&entry, rest, matches)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Matches `*` (any run of characters) and `?` (any single character).
+pub fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((bp, bn)) = backtrack {
+            p = bp + 1;
+            n = bn + 1;
+            backtrack = Some((bp, bn + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn wildcards() {
+        assert!(wildcard_match("*.binpack", "a.binpack"));
+        assert!(wildcard_match("*.binpack", ".binpack"));
+        assert!(wildcard_match("data-?.bin", "data-7.bin"));
+        assert!(wildcard_match("*-*-*", "a-b-c-d"));
+        assert!(wildcard_match("literal.bin", "literal.bin"));
+
+        assert!(!wildcard_match("*.binpack", "a.binpack.tmp"));
+        assert!(!wildcard_match("data-?.bin", "data-10.bin"));
+        assert!(!wildcard_match("literal.bin", "literal.bi"));
+    }
+
+    #[test]
+    fn globs() {
+        let dir = TempDir::new("glob");
+
+        for file in ["a.binpack", "b.binpack", "c.txt", "sub/d.binpack", "sub/deeper/e.binpack", "sub/f1.bin"] {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, file).unwrap();
+        }
+
+        let found = |pattern: &str| -> Vec<String> {
+            let files = glob(&format!("{}/{pattern}", dir.to_str())).unwrap();
+            files.iter().map(|path| path.strip_prefix(dir.path()).unwrap().to_string_lossy().into_owned()).collect()
+        };
+
+        assert_eq!(found("*.binpack"), ["a.binpack", "b.binpack"]);
+        assert_eq!(found("?.txt"), ["c.txt"]);
+        assert_eq!(found("sub/f?.bin"), ["sub/f1.bin"]);
+        assert_eq!(found("**/*.binpack"), ["a.binpack", "b.binpack", "sub/d.binpack", "sub/deeper/e.binpack"]);
+        assert!(found("*.missing").is_empty());
+        assert!(found("nowhere/*.binpack").is_empty());
+
+        // a path without wildcards matches only itself
+        assert_eq!(found("sub/d.binpack"), ["sub/d.binpack"]);
+        assert!(found("sub/z.binpack").is_empty());
+    }
+}
@@ -0,0 +1,190 @@
+use std::{
+    io::{self, Cursor, Write},
+    ops::Range,
+};
+
+use montyformat::{
+    chess::{Castling, Move, Position, Side},
+    FastDeserialise, MontyFormat, MontyValueFormat,
+};
+
+/// Centipawn scale relating `MontyFormat` expected scores to centipawns.
+pub const EVAL_SCALE: f32 = 400.0;
+
+/// One move of a game, in a form shared by both binpack formats.
+pub struct Ply<'a> {
+    pub best_move: Move,
+    /// Centipawns for `MontyValueFormat`, expected score for `MontyFormat`.
+    pub score: f32,
+    pub visits: Option<&'a [(Move, u32)]>,
+}
+
+/// Access to the games of a binpack format, for tools that work on both.
+pub trait GameFormat: FastDeserialise + Sized {
+    const NAME: &'static str;
+
+    fn decode(bytes: &[u8]) -> io::Result<Self>;
+
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()>;
+
+    fn startpos(&self) -> Position;
+
+    fn castling(&self) -> Castling;
+
+    /// White-relative.
+    fn result(&self) -> f32;
+
+    fn plies(&self) -> Vec<Ply<'_>>;
+
+    fn score_in_range(score: f32) -> bool;
+
+    /// Side-to-move centipawns of a stored score.
+    fn centipawns(score: f32) -> f32;
+
+    /// Keeps only the moves in `plies`, starting the game from the position
+    /// the first of them is played from.
+    fn keep_plies(&mut self, plies: Range<usize>);
+}
+
+impl GameFormat for MontyFormat {
+    const NAME: &'static str = "MontyFormat";
+
+    fn decode(bytes: &[u8]) -> io::Result<Self> {
+        MontyFormat::deserialise_from(&mut Cursor::new(bytes))
+    }
+
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        let mut buffer = Vec::new();
+        self.serialise_into_buffer(&mut buffer)?;
+        writer.write_all(&buffer)
+    }
+
+    fn startpos(&self) -> Position {
+        self.startpos
+    }
+
+    fn castling(&self) -> Castling {
+        self.castling
+    }
+
+    fn result(&self) -> f32 {
+        self.result
+    }
+
+    fn plies(&self) -> Vec<Ply<'_>> {
+        self.moves
+            .iter()
+            .map(|data| Ply {
+                best_move: data.best_move,
+                score: data.score,
+                visits: data.visit_distribution.as_deref(),
+            })
+            .collect()
+    }
+
+    fn score_in_range(score: f32) -> bool {
+        (0.0..=1.0).contains(&score)
+    }
+
+    fn centipawns(score: f32) -> f32 {
+        -EVAL_SCALE * (1.0 / score.clamp(0.0001, 0.9999) - 1.0).ln()
+    }
+
+    fn keep_plies(&mut self, plies: Range<usize>) {
+        self.moves.truncate(plies.end);
+
+        for data in self.moves.drain(..plies.start) {
+            self.startpos.make(data.best_move, &self.castling);
+        }
+    }
+}
+
+impl GameFormat for MontyValueFormat {
+    const NAME: &'static str = "MontyValueFormat";
+
+    fn decode(bytes: &[u8]) -> io::Result<Self> {
+        MontyValueFormat::deserialise_from(&mut Cursor::new(bytes), Vec::new())
+    }
+
+    fn encode(&self, writer: &mut impl Write) -> io::Result<()> {
+        MontyValueFormat::serialise_into(self, writer)
+    }
+
+    fn startpos(&self) -> Position {
+        self.startpos
+    }
+
+    fn castling(&self) -> Castling {
+        self.castling
+    }
+
+    fn result(&self) -> f32 {
+        self.result
+    }
+
+    fn plies(&self) -> Vec<Ply<'_>> {
+        self.moves
+            .iter()
+            .map(|data| Ply { best_move: data.best_move, score: f32::from(data.score), visits: None })
+            .collect()
+    }
+
+    fn score_in_range(score: f32) -> bool {
+        score != f32::from(i16::MIN)
+    }
+
+    fn centipawns(score: f32) -> f32 {
+        score
+    }
+
+    fn keep_plies(&mut self, plies: Range<usize>) {
+        self.moves.truncate(plies.end);
+
+        for data in self.moves.drain(..plies.start) {
+            self.startpos.make(data.best_move, &self.castling);
+        }
+    }
+}
+
+const ZOBRIST: [[u64; 64]; 12] = zobrist_table();
+const ZOBRIST_STM: u64 = 0xF3A1_5C2D_7B49_E086;
+
+const fn zobrist_table() -> [[u64; 64]; 12] {
+    let mut table = [[0; 64]; 12];
+    let mut seed = 0x9E37_79B9_7F4A_7C15u64;
+
+    let mut piece = 0;
+    while piece < 12 {
+        let mut sq = 0;
+        while sq < 64 {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            table[piece][sq] = seed;
+            sq += 1;
+        }
+        piece += 1;
+    }
+
+    table
+}
+
+/// Zobrist key of the pieces and side to move. Castling rights and en passant
+/// are ignored, so transpositions that differ only in those share a key.
+pub fn position_key(pos: &Position) -> u64 {
+    let bbs = pos.bbs();
+    let mut key = if pos.stm() == Side::BLACK { ZOBRIST_STM } else { 0 };
+
+    for (colour, &colour_bb) in bbs[..2].iter().enumerate() {
+        for (piece, &piece_bb) in bbs[2..].iter().enumerate() {
+            let mut bb = colour_bb & piece_bb;
+
+            while bb > 0 {
+                key ^= ZOBRIST[6 * colour + piece][bb.trailing_zeros() as usize];
+                bb &= bb - 1;
+            }
+        }
+    }
+
+    key
+}
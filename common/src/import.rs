@@ -0,0 +1,311 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, BufRead, Write},
+};
+
+use montyformat::{
+    chess::{Move, Side},
+    MontyValueFormat, SearchResult,
+};
+
+use crate::{
+    cli::{self, Cli},
+    epd::{self, Epd},
+    fen,
+    pgn::{self, PgnReader},
+    progress::Progress,
+    san,
+};
+
+/// Mates are imported as this many centipawns, less the distance to mate.
+const MATE_SCORE: f32 = 3000.0;
+/// Skipped games or lines to print individually before only counting them.
+const MAX_REPORTS: usize = 16;
+
+#[derive(Default)]
+struct Stats {
+    games: u64,
+    positions: u64,
+    skipped: BTreeMap<&'static str, u64>,
+    reported: usize,
+}
+
+impl Stats {
+    fn skip(&mut self, line: usize, reason: &'static str, detail: String) {
+        *self.skipped.entry(reason).or_default() += 1;
+
+        if self.reported < MAX_REPORTS {
+            println!("Skipped line {line}: {reason}: {detail}");
+            self.reported += 1;
+        }
+    }
+}
+
+/// Entry point for the `import` binary.
+pub fn main(name: &'static str, about: &'static str) {
+    let args = Cli::new(name, about)
+        .required("input", "PGN or EPD file to read")
+        .required("output", "MontyValueFormat binpack to write")
+        .optional("format", "'pgn' or 'epd', guessed from the input extension if not given")
+        .parse();
+
+    let inp_path = args.get("input");
+    let out_path = args.get("output");
+
+    let format = args.get_opt("format").map(str::to_string).unwrap_or_else(|| {
+        let ext = std::path::Path::new(inp_path).extension().and_then(|ext| ext.to_str()).unwrap_or("");
+        if ext.eq_ignore_ascii_case("pgn") { "pgn" } else { "epd" }.to_string()
+    });
+
+    let reader = cli::open_input(inp_path);
+    let mut writer = cli::create_output(out_path, &[inp_path]);
+    let mut stats = Stats::default();
+
+    let result = match format.as_str() {
+        "pgn" => import_pgn(reader, &mut writer, &mut stats),
+        "epd" => import_epd(reader, &mut writer, &mut stats),
+        _ => cli::fail(format!("unknown format '{format}', expected 'pgn' or 'epd'")),
+    };
+
+    result
+        .and_then(|_| writer.flush())
+        .unwrap_or_else(|err| cli::fail(format!("could not import '{inp_path}' into '{out_path}': {err}")));
+
+    println!("Imported {} Games, {} Positions", stats.games, stats.positions);
+    println!("Skipped  {}", stats.skipped.values().sum::<u64>());
+
+    for (reason, count) in &stats.skipped {
+        println!(" - {reason}: {count}");
+    }
+}
+
+/// Stores side-to-move centipawns as they are. `MontyValueFormat::push` takes
+/// a white-relative expected score instead, and would round the score off on
+/// the way through it.
+fn push(value: &mut MontyValueFormat, best_move: Move, cp: f32) {
+    let score = cp.round().clamp(-f32::from(i16::MAX), f32::from(i16::MAX)) as i16;
+    value.moves.push(SearchResult { best_move, score });
+}
+
+fn parse_result(result: &str) -> Option<f32> {
+    match result.trim().trim_matches('"') {
+        "1-0" | "1.0" | "1" => Some(1.0),
+        "0-1" | "0.0" | "0" => Some(0.0),
+        "1/2-1/2" | "0.5" => Some(0.5),
+        _ => None,
+    }
+}
+
+fn import_pgn(reader: impl BufRead, writer: &mut impl Write, stats: &mut Stats) -> io::Result<()> {
+    let mut reader = PgnReader::new(reader);
+    let mut progress = Progress::counter("Imported", "Games", 16384);
+
+    while let Some(game) = reader.next_game()? {
+        match convert_pgn(&game) {
+            Ok(value) => {
+                value.serialise_into(writer)?;
+                stats.games += 1;
+                stats.positions += value.moves.len() as u64;
+                progress.update(stats.games);
+            }
+            Err((reason, detail)) => stats.skip(game.line, reason, detail),
+        }
+    }
+
+    Ok(())
+}
+
+/// Leading moves without an eval, such as book moves, become part of the
+/// start position. After that every move needs one.
+fn convert_pgn(game: &pgn::PgnGame) -> Result<MontyValueFormat, (&'static str, String)> {
+    let result = game.tag("Result").or(game.result.as_deref()).unwrap_or("*");
+    let result = parse_result(result).ok_or(("unknown result", result.to_string()))?;
+
+    let fen = game.tag("FEN").unwrap_or(pgn::STARTPOS);
+    let (mut pos, castling) = fen::parse(fen).map_err(|err| ("invalid position", format!("{fen}: {err}")))?;
+
+    let mut value = MontyValueFormat { startpos: pos, castling, result, moves: Vec::new() };
+
+    for (ply, (text, comment)) in game.moves.iter().enumerate() {
+        let mov = san::parse_move(&pos, &castling, text).ok_or(("illegal move", format!("{text} at ply {ply}")))?;
+        let eval = comment.as_deref().and_then(|comment| pgn::parse_eval(comment, MATE_SCORE));
+
+        match eval {
+            Some(cp) => {
+                let cp = if pos.stm() == Side::BLACK { -cp } else { cp };
+                push(&mut value, mov, cp);
+            }
+            None if value.moves.is_empty() => {
+                value.startpos.make(mov, &castling);
+            }
+            None => return Err(("missing eval", format!("{text} at ply {ply}"))),
+        }
+
+        pos.make(mov, &castling);
+    }
+
+    if value.moves.is_empty() {
+        return Err(("no evals", format!("{} moves", game.moves.len())));
+    }
+
+    Ok(value)
+}
+
+fn import_epd(reader: impl BufRead, writer: &mut impl Write, stats: &mut Stats) -> io::Result<()> {
+    let mut progress = Progress::counter("Imported", "Positions", 1024 * 1024);
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        match convert_epd(&line) {
+            Ok(value) => {
+                value.serialise_into(writer)?;
+                stats.games += 1;
+                stats.positions += 1;
+                progress.update(stats.positions);
+            }
+            Err((reason, detail)) => stats.skip(index + 1, reason, detail),
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads either `<fen> | <score> | <result> | <best move>`, as written by
+/// `export`, or EPD with `bm`, `ce` and [`epd::RESULT`] opcodes. Scores are
+/// centipawns for the side to move, results are white-relative.
+fn convert_epd(line: &str) -> Result<MontyValueFormat, (&'static str, String)> {
+    let (fen, score, result, best) = if line.contains('|') {
+        let mut fields = line.split('|').map(|field| field.trim().to_string());
+        (fields.next().unwrap_or_default(), fields.next(), fields.next(), fields.next())
+    } else {
+        let epd = Epd::parse(line).ok_or(("invalid position", line.to_string()))?;
+        let get = |code: &str| epd.get(code).map(str::to_string);
+        (epd.fen.clone(), get("ce"), get(epd::RESULT), get("bm"))
+    };
+
+    let score = score.and_then(|score| score.parse::<f32>().ok()).ok_or(("missing score", line.to_string()))?;
+    let result = result.as_deref().and_then(parse_result).ok_or(("missing result", line.to_string()))?;
+    let best = best
+        .as_deref()
+        .and_then(|best| best.split_whitespace().next())
+        .ok_or(("missing best move", line.to_string()))?;
+
+    let (pos, castling) = fen::parse(&fen).map_err(|err| ("invalid position", format!("{fen}: {err}")))?;
+    let mov = san::parse_move(&pos, &castling, best).ok_or(("illegal move", line.to_string()))?;
+
+    let mut value = MontyValueFormat { startpos: pos, castling, result, moves: Vec::new() };
+    push(&mut value, mov, score);
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        export::{self, Style},
+        format,
+        testing::{self, STARTPOS},
+    };
+
+    fn import(
+        text: &str,
+        import: fn(&[u8], &mut Vec<u8>, &mut Stats) -> io::Result<()>,
+    ) -> (Vec<MontyValueFormat>, Stats) {
+        let mut bytes = Vec::new();
+        let mut stats = Stats::default();
+        import(text.as_bytes(), &mut bytes, &mut stats).unwrap();
+        (testing::read_value_games(&bytes), stats)
+    }
+
+    fn round_trip(style: Style) {
+        let games = [
+            testing::value_game(STARTPOS, &["e4", "c5", "Nf3"], 1.0),
+            testing::value_game("r3k3/8/8/8/8/8/8/4K2R w Kq - 0 1", &["O-O", "O-O-O"], 0.5),
+        ];
+
+        let mut text = Vec::new();
+        let bytes = testing::binpack(&games);
+        export::export::<MontyValueFormat>(&bytes[..], &mut text, style, 0..u64::MAX, None).unwrap();
+
+        let (imported, stats) =
+            import(std::str::from_utf8(&text).unwrap(), |reader, writer, stats| import_epd(reader, writer, stats));
+
+        assert!(stats.skipped.is_empty());
+        assert_eq!((stats.games, stats.positions), (5, 5));
+
+        // each position becomes a game of one move
+        let mut imported = imported.iter();
+
+        for game in &games {
+            let mut pos = game.startpos;
+
+            for data in &game.moves {
+                let position = imported.next().unwrap();
+
+                assert_eq!(position.startpos.as_fen(), pos.as_fen());
+                assert_eq!(position.castling.rook_files(), game.castling.rook_files());
+                assert_eq!(position.result, game.result);
+                assert_eq!(position.moves.len(), 1);
+                assert_eq!(position.moves[0].best_move, data.best_move);
+                assert_eq!(position.moves[0].score, data.score);
+
+                pos.make(data.best_move, &game.castling);
+            }
+        }
+
+        assert!(imported.next().is_none());
+    }
+
+    #[test]
+    fn imports_exported_epd() {
+        round_trip(Style::Epd);
+    }
+
+    #[test]
+    fn imports_exported_text() {
+        round_trip(Style::Text);
+    }
+
+    #[test]
+    fn imports_pgn_evals_for_the_side_to_move() {
+        let pgn =
+            "[Result \"0-1\"]\n\n1. e4 e5 2. Nf3 { [%eval 0.31] } Nc6 { [%eval -1.5] } 3. Bc4 { [%eval #-2] } 0-1\n";
+
+        let (imported, stats) = import(pgn, |reader, writer, stats| import_pgn(reader, writer, stats));
+
+        assert_eq!((stats.games, stats.positions), (1, 3));
+        let game = &imported[0];
+
+        // the moves without an eval are played into the start position
+        let (after, _) = fen::parse("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2").unwrap();
+        assert_eq!(format::position_key(&game.startpos), format::position_key(&after));
+        assert_eq!(game.result, 0.0);
+
+        let scores = game.moves.iter().map(|data| data.score).collect::<Vec<_>>();
+        assert_eq!(scores, [31, 150, -(MATE_SCORE as i16 - 2)]);
+    }
+
+    #[test]
+    fn skips_bad_lines() {
+        let epd = [
+            "8/8/8/8/8/8/8/K6k w - - bm Kb1;",
+            "8/8/8/8/8/8/8/K6k w - - ce 5; bm Kb1; c9 \"*\";",
+            "8/8/8/8/8/8/8/K6k w - - ce 5; bm Kh2; c9 \"1-0\";",
+            "8/8/8/8/8/8/8/K6k w - - ce 5; bm Kb1; c9 \"1-0\";",
+        ]
+        .join("\n");
+
+        let (imported, stats) = import(&epd, |reader, writer, stats| import_epd(reader, writer, stats));
+
+        assert_eq!(imported.len(), 1);
+        assert_eq!(stats.skipped.get("missing score"), Some(&1));
+        assert_eq!(stats.skipped.get("missing result"), Some(&1));
+        assert_eq!(stats.skipped.get("illegal move"), Some(&1));
+    }
+}
@@ -0,0 +1,406 @@
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{self, BufReader, Write},
+};
+
+use montyformat::FastDeserialise;
+
+use crate::{
+    cli::{self, Cli},
+    files,
+    progress::Progress,
+    rng::{self, Rand},
+};
+
+/// An input file and how many times its games should appear in the output.
+/// The fractional part of the weight is the probability that each game gets
+/// one extra copy, so e.g. `0.5` halves a dataset and `2.5` upsamples it.
+#[derive(Clone, Debug)]
+pub struct Source {
+    pub path: String,
+    pub weight: f64,
+}
+
+/// Entry point for the `interleave` binaries of each format.
+pub fn main<T: FastDeserialise>(name: &'static str, about: &'static str, default_output: &'static str) {
+    let args = Cli::new(name, about)
+        .multiple("input", "Binpack, folder or glob to read from, optionally suffixed by ':<weight>'")
+        .default("output", default_output, "Binpack to write")
+        .flag("recursive", "Also read .binpack files in subfolders of folder inputs")
+        .optional("seed", "Seed for reproducible output, random if not given")
+        .optional("validation", "Binpack to divert validation games into")
+        .default("validation-fraction", "0.01", "Fraction of games to divert into the validation binpack")
+        .optional("validation-games", "Expected number of games in the validation binpack, replaces the fraction")
+        .default("split-seed", "0", "Seed for choosing validation games, independent of --seed")
+        .parse();
+
+    let sources = parse_sources(args.get_all("input"), args.flag("recursive"));
+    let output = args.get("output");
+
+    if sources.is_empty() {
+        cli::fail("no input files given");
+    }
+
+    let seed = args.parse_opt("seed").unwrap_or_else(|| Rand::from_clock().rand());
+
+    println!("Writing to {output:#?}");
+    println!("Reading from:");
+    for source in &sources {
+        println!("  {} (x{})", source.path, source.weight);
+    }
+    println!("Seed: {seed}");
+
+    let mut input_refs = sources.iter().map(|source| source.path.as_str()).collect::<Vec<_>>();
+    let mut writer = cli::create_output(output, &input_refs);
+    let mut rng = Rand::new(seed);
+
+    let mut split = args.get_opt("validation").map(|path| {
+        let mut fraction: f64 = args.parse("validation-fraction");
+        if !(0.0..=1.0).contains(&fraction) {
+            cli::fail("--validation-fraction must be in [0, 1]");
+        }
+
+        if path == output {
+            cli::fail("--validation must differ from --output");
+        }
+
+        if let Some(limit) = args.parse_opt::<u64>("validation-games") {
+            let games = count_games::<T>(&sources).unwrap_or_else(|err| cli::fail(format!("counting failed: {err}")));
+            fraction = fraction_of(limit, games);
+        }
+
+        println!("Validation: {path:#?} ({fraction} of games)");
+
+        input_refs.push(output);
+        Split::new(cli::create_output(path, &input_refs), fraction, args.parse("split-seed"))
+    });
+
+    let result = interleave::<T>(&sources, &mut writer, &mut rng, split.as_mut()).and_then(|_| writer.flush());
+    let result = result.and_then(|_| split.as_mut().map_or(Ok(()), |split| split.writer.flush()));
+
+    if let Err(err) = result {
+        cli::fail(format!("interleaving failed: {err}"));
+    }
+}
+
+/// Fraction of `games` that diverts `wanted` of them in expectation.
+pub fn fraction_of(wanted: u64, games: u64) -> f64 {
+    (wanted as f64 / games.max(1) as f64).min(1.0)
+}
+
+/// Diverts games into a validation binpack. Games are chosen by hashing their
+/// bytes, so a game lands in the same split on every run with the same
+/// `seed`, regardless of input order or the interleaving seed.
+///
+/// There is deliberately no hard cap on the number of games, as stopping once
+/// it is reached would make the split depend on the interleaving order. A
+/// game count given by `--validation-games` is instead turned into a fraction
+/// of the games in the inputs, so it is only met in expectation.
+pub struct Split<W> {
+    pub writer: W,
+    fraction: f64,
+    seed: u64,
+    taken: HashSet<u64>,
+    pub games: u64,
+    pub bytes: u64,
+}
+
+impl<W: Write> Split<W> {
+    pub fn new(writer: W, fraction: f64, seed: u64) -> Self {
+        Self { writer, fraction, seed, taken: HashSet::new(), games: 0, bytes: 0 }
+    }
+
+    /// Writes `game` to the validation set if it belongs there, returning
+    /// whether it was consumed. Further copies of a taken game (from weighted
+    /// sources) are dropped so they cannot leak into the main output.
+    pub fn divert(&mut self, game: &[u8]) -> io::Result<bool> {
+        let hash = rng::hash_bytes(game);
+
+        if self.taken.contains(&hash) {
+            return Ok(true);
+        }
+
+        if Rand::new(hash ^ self.seed).float() >= self.fraction {
+            return Ok(false);
+        }
+
+        self.writer.write_all(game)?;
+        self.taken.insert(hash);
+        self.games += 1;
+        self.bytes += game.len() as u64;
+
+        Ok(true)
+    }
+}
+
+/// Splits off an optional `:<weight>` suffix and expands each input.
+pub fn parse_sources(args: &[String], recursive: bool) -> Vec<Source> {
+    let mut sources = Vec::new();
+
+    for arg in args {
+        let (pattern, weight) = match arg.rsplit_once(':').map(|(path, weight)| (path, weight.parse::<f64>())) {
+            Some((path, Ok(weight))) => (path.to_string(), weight),
+            _ => (arg.clone(), 1.0),
+        };
+
+        if !weight.is_finite() || weight < 0.0 {
+            cli::fail(format!("invalid weight in '{arg}'"));
+        }
+
+        for path in files::expand_inputs(&[pattern], recursive, "binpack") {
+            sources.push(Source { path, weight });
+        }
+    }
+
+    sources
+}
+
+/// Number of distinct games in the sources, counting each file once
+/// whatever its weight.
+pub fn count_games<T: FastDeserialise>(sources: &[Source]) -> io::Result<u64> {
+    let mut paths = sources.iter().map(|source| source.path.as_str()).collect::<Vec<_>>();
+    paths.sort_unstable();
+    paths.dedup();
+
+    let mut games = 0;
+    let mut buffer = Vec::new();
+
+    for path in paths {
+        let file = File::open(path)?;
+        let mut remaining = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+
+        while remaining > 0 {
+            T::deserialise_fast_into_buffer(&mut reader, &mut buffer)?;
+            remaining = remaining.saturating_sub(buffer.len() as u64);
+            games += 1;
+        }
+    }
+
+    Ok(games)
+}
+
+struct Stream {
+    remaining: u64,
+    keep: f64,
+    reader: BufReader<File>,
+}
+
+/// Writes the games of every source according to its weight, picking the
+/// next stream with probability proportional to its remaining bytes. Returns
+/// the number of bytes written to the main output.
+pub fn interleave<T: FastDeserialise>(
+    sources: &[Source],
+    writer: &mut impl Write,
+    rng: &mut Rand,
+    mut split: Option<&mut Split<impl Write>>,
+) -> io::Result<u64> {
+    let mut streams = Vec::new();
+    let mut total = 0;
+
+    for source in sources {
+        let size = File::open(&source.path)?.metadata()?.len();
+
+        if size == 0 {
+            continue;
+        }
+
+        let repeats = source.weight.floor() as usize;
+        let fraction = source.weight - source.weight.floor();
+
+        for keep in std::iter::repeat_n(1.0, repeats).chain((fraction > 0.0).then_some(fraction)) {
+            streams.push(Stream { remaining: size, keep, reader: BufReader::new(File::open(&source.path)?) });
+            total += size;
+        }
+    }
+
+    let mut remaining = total;
+    let mut written = 0;
+    let mut progress = Progress::fraction("Read", "Bytes", total, 1024 * 1024 * 256);
+
+    let mut buffer = Vec::new();
+
+    while remaining > 0 {
+        let mut spot = rng.rand() % remaining;
+        let mut idx = 0;
+        while streams[idx].remaining <= spot {
+            spot -= streams[idx].remaining;
+            idx += 1;
+        }
+
+        let stream = &mut streams[idx];
+
+        T::deserialise_fast_into_buffer(&mut stream.reader, &mut buffer)?;
+
+        if stream.keep >= 1.0 || rng.float() < stream.keep {
+            let diverted = match split.as_deref_mut() {
+                Some(split) => split.divert(&buffer)?,
+                None => false,
+            };
+
+            if !diverted {
+                writer.write_all(&buffer)?;
+                written += buffer.len() as u64;
+            }
+        }
+
+        let size = buffer.len() as u64;
+
+        remaining -= size;
+        stream.remaining -= size;
+        if stream.remaining == 0 {
+            streams.swap_remove(idx);
+        }
+
+        progress.update(total - remaining);
+    }
+
+    progress.finish();
+    println!("Written {written} Bytes");
+
+    if let Some(split) = split {
+        println!("Validation: {} Games, {} Bytes", split.games, split.bytes);
+    }
+
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufRead;
+
+    use super::*;
+    use crate::testing::TempDir;
+
+    /// Length-prefixed records standing in for a binpack format.
+    struct Record;
+
+    impl FastDeserialise for Record {
+        fn deserialise_fast_into_buffer(reader: &mut impl BufRead, buffer: &mut Vec<u8>) -> io::Result<()> {
+            let mut len = [0; 2];
+            reader.read_exact(&mut len)?;
+
+            buffer.clear();
+            buffer.extend_from_slice(&len);
+            buffer.resize(2 + usize::from(u16::from_le_bytes(len)), 0);
+            reader.read_exact(&mut buffer[2..])
+        }
+    }
+
+    fn records(mut bytes: &[u8]) -> Vec<Vec<u8>> {
+        let mut records = Vec::new();
+
+        while !bytes.is_empty() {
+            let mut record = Vec::new();
+            Record::deserialise_fast_into_buffer(&mut bytes, &mut record).unwrap();
+            records.push(record);
+        }
+
+        records
+    }
+
+    /// Writes `count` records of varying length per file and returns the
+    /// sources along with every record written.
+    fn sources(name: &str, counts: &[usize]) -> (TempDir, Vec<Source>, Vec<Vec<u8>>) {
+        let dir = TempDir::new(name);
+
+        let mut sources = Vec::new();
+        let mut all = Vec::new();
+
+        for (file, &count) in counts.iter().enumerate() {
+            let path = dir.join(format!("{file}.binpack"));
+            let mut bytes = Vec::new();
+
+            for game in 0..count {
+                let payload = format!("file {file} game {game}{}", "x".repeat(game % 7));
+                let mut record = (payload.len() as u16).to_le_bytes().to_vec();
+                record.extend_from_slice(payload.as_bytes());
+
+                bytes.extend_from_slice(&record);
+                all.push(record);
+            }
+
+            std::fs::write(&path, bytes).unwrap();
+            sources.push(Source { path: path.to_string_lossy().into_owned(), weight: 1.0 });
+        }
+
+        (dir, sources, all)
+    }
+
+    fn run(sources: &[Source], seed: u64, split: Option<&mut Split<Vec<u8>>>) -> Vec<u8> {
+        let mut output = Vec::new();
+        interleave::<Record>(sources, &mut output, &mut Rand::new(seed), split).unwrap();
+        output
+    }
+
+    #[test]
+    fn output_is_a_permutation_of_the_inputs() {
+        let (_dir, sources, mut expected) = sources("interleave-permutation", &[50, 1, 0, 200]);
+
+        let output = run(&sources, 42, None);
+        let mut games = records(&output);
+
+        assert_eq!(output.len(), expected.iter().map(Vec::len).sum::<usize>());
+        assert_ne!(games, expected, "games were not interleaved");
+
+        games.sort();
+        expected.sort();
+        assert_eq!(games, expected);
+    }
+
+    #[test]
+    fn reproducible_for_a_fixed_seed() {
+        let (_dir, sources, _) = sources("interleave-seed", &[30, 40, 50]);
+
+        assert_eq!(run(&sources, 7, None), run(&sources, 7, None));
+        assert_ne!(run(&sources, 7, None), run(&sources, 8, None));
+    }
+
+    #[test]
+    fn counts_each_file_once() {
+        let (_dir, mut sources, _) = sources("interleave-count", &[12, 0, 30]);
+        sources.push(Source { weight: 2.5, ..sources[2].clone() });
+
+        assert_eq!(count_games::<Record>(&sources).unwrap(), 42);
+    }
+
+    #[test]
+    fn split_is_independent_of_the_interleaving_seed() {
+        let (_dir, sources, mut expected) = sources("interleave-split", &[100, 100]);
+        let mut validation = Vec::new();
+
+        for seed in [1, 2] {
+            let mut split = Split::new(Vec::new(), 0.2, 3);
+            let output = run(&sources, seed, Some(&mut split));
+
+            let mut diverted = records(&split.writer);
+            diverted.sort();
+            validation.push(diverted.clone());
+
+            let mut games = records(&output);
+            games.extend(diverted);
+            games.sort();
+            expected.sort();
+            assert_eq!(games, expected);
+        }
+
+        assert!(!validation[0].is_empty());
+        assert_eq!(validation[0], validation[1]);
+    }
+
+    #[test]
+    fn validation_games_can_exceed_the_default_fraction() {
+        let (_dir, sources, _) = sources("interleave-validation-games", &[600, 400]);
+        let games = count_games::<Record>(&sources).unwrap();
+
+        for wanted in [0, 5, 200, 1000, 5000] {
+            let mut split = Split::new(Vec::new(), fraction_of(wanted, games), 11);
+            run(&sources, 1, Some(&mut split));
+
+            let expected = wanted.min(games) as f64;
+            let diverted = split.games as f64;
+            assert!((diverted - expected).abs() <= 3.0 * expected.sqrt() + 1.0, "{wanted}: {diverted}");
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use std::{
+    io,
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// Written into checkpoints saved on interruption, so a run can resume there.
+pub const PROGRESS_FILE: &str = "progress.txt";
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Catches SIGINT and SIGTERM so the trainer can stop at a safe point, see
+/// [`requested`]. A second signal exits immediately.
+pub fn install() {
+    ctrlc::set_handler(|| {
+        if REQUESTED.swap(true, Ordering::SeqCst) {
+            eprintln!("Interrupted again, exiting without a checkpoint");
+            std::process::exit(130);
+        }
+
+        eprintln!("Interrupted, saving a checkpoint at the next safe point (interrupt again to exit now)");
+    })
+    .expect("Could not install the signal handler!");
+}
+
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Where a run resumed from a checkpoint starts. Runs resume at superbatch
+/// granularity: the data loaders stream from the start of their files and
+/// cannot skip ahead, so an unfinished superbatch is trained again from its
+/// first batch.
+#[derive(Clone, Copy, Debug)]
+pub struct Progress {
+    pub next_superbatch: usize,
+}
+
+impl Progress {
+    /// Written once `superbatch` has finished.
+    pub fn finished(superbatch: usize) -> Self {
+        Self { next_superbatch: superbatch + 1 }
+    }
+
+    /// Written when training is interrupted part way through `superbatch`.
+    pub fn interrupted(superbatch: usize) -> Self {
+        Self { next_superbatch: superbatch }
+    }
+
+    pub fn write(&self, dir: &str) -> io::Result<()> {
+        let text = format!("next_superbatch={}\n", self.next_superbatch);
+        std::fs::write(Path::new(dir).join(PROGRESS_FILE), text)
+    }
+
+    pub fn read(dir: &str) -> io::Result<Self> {
+        let path = Path::new(dir).join(PROGRESS_FILE);
+        let text = std::fs::read_to_string(&path)?;
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("invalid {}", path.display()));
+
+        let field = |key: &str| -> io::Result<usize> {
+            text.lines()
+                .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+                .and_then(|value| value.trim().parse().ok())
+                .ok_or_else(invalid)
+        };
+
+        Ok(Self { next_superbatch: field("next_superbatch")? })
+    }
+}
@@ -0,0 +1,26 @@
+pub mod buckets;
+pub mod checkpoints;
+pub mod cli;
+pub mod dedup;
+pub mod epd;
+pub mod export;
+pub mod fen;
+pub mod files;
+pub mod format;
+pub mod import;
+pub mod interleave;
+pub mod interrupt;
+pub mod netdiff;
+pub mod neurons;
+pub mod pgn;
+pub mod plot;
+pub mod progress;
+pub mod rng;
+pub mod runlog;
+pub mod sample;
+pub mod san;
+pub mod verify;
+pub mod weights;
+
+#[cfg(test)]
+mod testing;
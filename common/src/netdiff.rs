@@ -0,0 +1,50 @@
+/// Both trainers clip weights to this, see their `AdamWParams`.
+pub const MAX_WEIGHT: f32 = 0.99;
+
+/// Weights within half a quantisation step of the clip count as saturated.
+/// The coarsest scale either net is saved with is the policy's 128, where the
+/// clip rounds up to 127 / 128.
+const SATURATION_TOLERANCE: f32 = 1.0 / 256.0;
+
+pub struct TensorStats {
+    pub norm: f64,
+    pub max_abs: f32,
+    pub saturated: f64,
+}
+
+impl TensorStats {
+    pub fn of(weights: &[f32]) -> Self {
+        let norm = weights.iter().map(|&w| f64::from(w).powi(2)).sum::<f64>().sqrt();
+        let max_abs = weights.iter().fold(0.0f32, |max, w| max.max(w.abs()));
+        let saturated = weights.iter().filter(|w| w.abs() >= MAX_WEIGHT - SATURATION_TOLERANCE).count();
+
+        Self { norm, max_abs, saturated: saturated as f64 / weights.len().max(1) as f64 }
+    }
+}
+
+pub fn print_header() {
+    println!(
+        "{:<8} {:>10} {:>10} {:>10} {:>7} {:>7} {:>8} {:>8} {:>10} {:>8}",
+        "Tensor", "Size", "Norm A", "Norm B", "Max A", "Max B", "Sat A", "Sat B", "Norm A-B", "Rel"
+    );
+}
+
+/// Prints one row of statistics for a pair of tensors of the same shape.
+pub fn print_tensor(name: &str, a: &[f32], b: &[f32]) {
+    let (stats_a, stats_b) = (TensorStats::of(a), TensorStats::of(b));
+    let diff = a.iter().zip(b).map(|(&x, &y)| f64::from(x - y).powi(2)).sum::<f64>().sqrt();
+    let rel = diff / stats_a.norm.max(f64::EPSILON);
+
+    println!(
+        "{name:<8} {:>10} {:>10.3} {:>10.3} {:>7.3} {:>7.3} {:>7.3}% {:>7.3}% {:>10.3} {:>8.4}",
+        a.len(),
+        stats_a.norm,
+        stats_b.norm,
+        stats_a.max_abs,
+        stats_b.max_abs,
+        100.0 * stats_a.saturated,
+        100.0 * stats_b.saturated,
+        diff,
+        rel
+    );
+}
@@ -0,0 +1,72 @@
+/// Counts how often each `l0` neuron is clipped by CReLU over a sample of
+/// positions, for a layer whose two halves are multiplied pairwise.
+pub struct NeuronStats {
+    positions: u64,
+    zero: Vec<u64>,
+    saturated: Vec<u64>,
+    /// Positions where each pairwise product is non-zero.
+    active: Vec<u64>,
+}
+
+impl NeuronStats {
+    pub fn new(hl: usize) -> Self {
+        assert_eq!(hl % 2, 0, "l0 must have an even number of neurons!");
+        Self { positions: 0, zero: vec![0; hl], saturated: vec![0; hl], active: vec![0; hl / 2] }
+    }
+
+    pub fn positions(&self) -> u64 {
+        self.positions
+    }
+
+    /// Takes `l0` after CReLU, scaled so that saturation is at `one`.
+    pub fn record<T: Copy + PartialOrd + Default>(&mut self, l0: &[T], one: T) {
+        let half = self.active.len();
+        assert_eq!(l0.len(), 2 * half, "l0 size does not match!");
+
+        for (i, &x) in l0.iter().enumerate() {
+            self.zero[i] += u64::from(x <= T::default());
+            self.saturated[i] += u64::from(x >= one);
+        }
+
+        for (j, active) in self.active.iter_mut().enumerate() {
+            *active += u64::from(l0[j] > T::default() && l0[j + half] > T::default());
+        }
+
+        self.positions += 1;
+    }
+
+    /// Pairs whose product was zero on every position, so removing them
+    /// does not change the output on the sample.
+    pub fn dead_pairs(&self) -> Vec<usize> {
+        (0..self.active.len()).filter(|&j| self.active[j] == 0).collect()
+    }
+
+    pub fn live_pairs(&self) -> Vec<usize> {
+        (0..self.active.len()).filter(|&j| self.active[j] > 0).collect()
+    }
+
+    pub fn print(&self) {
+        let half = self.active.len();
+        let pct = |n: usize, total: usize| 100.0 * n as f64 / total.max(1) as f64;
+        let count = |counts: &[u64]| counts.iter().filter(|&&n| n == self.positions).count();
+
+        println!("Positions         : {}", self.positions);
+        println!("{:<18}{:>18}{:>18}", "", "First Half", "Second Half");
+
+        for (name, counts) in [("Always Zero", &self.zero), ("Always Saturated", &self.saturated)] {
+            let (a, b) = (count(&counts[..half]), count(&counts[half..]));
+            println!("{name:<18}{a:>9} ({:>5.2}%){b:>9} ({:>5.2}%)", pct(a, half), pct(b, half));
+        }
+
+        // neurons that fire but are always multiplied by a zero partner
+        let masked = |offset: usize| {
+            (0..half).filter(|&j| self.active[j] == 0 && self.zero[j + offset] < self.positions).count()
+        };
+
+        let (a, b) = (masked(0), masked(half));
+        println!("{:<18}{a:>9} ({:>5.2}%){b:>9} ({:>5.2}%)", "Masked by Partner", pct(a, half), pct(b, half));
+
+        let dead = self.dead_pairs().len();
+        println!("Never Active      : {dead} / {half} ({:.2}%)", pct(dead, half));
+    }
+}
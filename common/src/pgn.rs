@@ -0,0 +1,551 @@
+use std::io::{self, BufRead, Write};
+
+use montyformat::chess::{Castling, Piece, Position, Side};
+
+use crate::{
+    cli::{self, Cli},
+    export, fen,
+    format::GameFormat,
+    san,
+};
+
+pub const STARTPOS: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+const LINE_WIDTH: usize = 80;
+
+/// Entry point for the `to-pgn` binaries of each format.
+pub fn main<T: GameFormat>(name: &'static str, about: &'static str) {
+    let args = Cli::new(name, about)
+        .required("input", "Binpack to read")
+        .optional("output", "PGN file to write, stdout if not given")
+        .optional("games", "Only convert games in this range of indices, e.g. '100..200', '..200' or '100..'")
+        .default("alternatives", "3", "Most visited moves to list in each comment, when visits are stored")
+        .default("event", "montytrain selfplay", "Event tag of every game")
+        .parse();
+
+    let inp_path = args.get("input");
+    let options = Options {
+        games: args.get_opt("games").map_or(Ok(0..u64::MAX), export::parse_range).unwrap_or_else(|err| cli::fail(err)),
+        alternatives: args.parse("alternatives"),
+        event: args.get("event"),
+    };
+
+    let reader = cli::open_input(inp_path);
+
+    let result = match args.get_opt("output") {
+        Some(out_path) => {
+            let mut writer = cli::create_output(out_path, &[inp_path]);
+            let games = write_pgns::<T>(reader, &mut writer, &options).and_then(|n| writer.flush().map(|_| n));
+            games.map(|games| println!("Written {games} Games"))
+        }
+        None => write_pgns::<T>(reader, &mut io::stdout().lock(), &options).map(|_| ()),
+    };
+
+    if let Err(err) = result.or_else(|err| if err.kind() == io::ErrorKind::BrokenPipe { Ok(()) } else { Err(err) }) {
+        cli::fail(format!("conversion failed: {err}"));
+    }
+}
+
+pub struct Options<'a> {
+    pub games: std::ops::Range<u64>,
+    pub alternatives: usize,
+    pub event: &'a str,
+}
+
+/// Writes the selected games as PGN, returning how many were written.
+pub fn write_pgns<T: GameFormat>(
+    mut reader: impl BufRead,
+    writer: &mut impl Write,
+    options: &Options,
+) -> io::Result<u64> {
+    let mut buffer = Vec::new();
+    let mut written = 0;
+
+    for index in 0..options.games.end {
+        if reader.fill_buf()?.is_empty() {
+            break;
+        }
+
+        T::deserialise_fast_into_buffer(&mut reader, &mut buffer)?;
+
+        if index >= options.games.start {
+            write_pgn(&T::decode(&buffer)?, index, writer, options)?;
+            written += 1;
+        }
+    }
+
+    Ok(written)
+}
+
+pub fn result_str(result: f32) -> &'static str {
+    match result {
+        r if r > 0.75 => "1-0",
+        r if r < 0.25 => "0-1",
+        _ => "1/2-1/2",
+    }
+}
+
+/// Whether a side that can still castle has its king off the e-file, or a
+/// castling rook off the a- or h-file, so the game needs Chess960 rules.
+fn is_chess960(pos: &Position, castling: &Castling) -> bool {
+    let fen = pos.as_fen();
+    let rights = fen.split_whitespace().nth(2).unwrap_or("-");
+
+    rights.chars().filter(|&c| c != '-').any(|right| {
+        let side = if right.is_ascii_uppercase() { Side::WHITE } else { Side::BLACK };
+        let king_file = (pos.piece(side) & pos.piece(Piece::KING)).trailing_zeros() % 8;
+
+        let (rook_file, standard) = match right.to_ascii_lowercase() {
+            'k' => (castling.rook_file(side, 1), 7),
+            'q' => (castling.rook_file(side, 0), 0),
+            _ => return true,
+        };
+
+        king_file != 4 || rook_file != standard
+    })
+}
+
+/// Replaces `KQkq` castling rights by the files of the castling rooks, as in
+/// Shredder-FEN, which Chess960-aware readers accept unambiguously.
+fn shredder_fen(fen: &str, castling: &Castling) -> String {
+    let mut fields = fen.split_whitespace().map(str::to_string).collect::<Vec<_>>();
+
+    if let Some(rights) = fields.get_mut(2) {
+        *rights = rights
+            .chars()
+            .map(|right| {
+                let side = if right.is_ascii_uppercase() { Side::WHITE } else { Side::BLACK };
+                let file = match right.to_ascii_lowercase() {
+                    'k' => castling.rook_file(side, 1),
+                    'q' => castling.rook_file(side, 0),
+                    _ => return right,
+                };
+
+                let file = char::from(b'a' + file as u8);
+                if side == Side::WHITE {
+                    file.to_ascii_uppercase()
+                } else {
+                    file
+                }
+            })
+            .collect();
+    }
+
+    fields.join(" ")
+}
+
+fn write_pgn<T: GameFormat>(game: &T, index: u64, writer: &mut impl Write, options: &Options) -> io::Result<()> {
+    let castling = game.castling();
+    let mut pos = game.startpos();
+    let start_fen = fen::write(&pos);
+    let result = result_str(game.result());
+
+    writeln!(writer, "[Event \"{}\"]", options.event)?;
+    writeln!(writer, "[Site \"?\"]")?;
+    writeln!(writer, "[Date \"????.??.??\"]")?;
+    writeln!(writer, "[Round \"{}\"]", index + 1)?;
+    writeln!(writer, "[White \"?\"]")?;
+    writeln!(writer, "[Black \"?\"]")?;
+    writeln!(writer, "[Result \"{result}\"]")?;
+
+    let chess960 = is_chess960(&pos, &castling);
+
+    if chess960 {
+        writeln!(writer, "[Variant \"Chess960\"]")?;
+    }
+
+    if chess960 || start_fen != STARTPOS {
+        let fen = if chess960 { shredder_fen(&start_fen, &castling) } else { start_fen.clone() };
+        writeln!(writer, "[SetUp \"1\"]")?;
+        writeln!(writer, "[FEN \"{fen}\"]")?;
+    }
+
+    writeln!(writer)?;
+
+    let mut fullmove = start_fen.split_whitespace().nth(5).and_then(|n| n.parse::<u32>().ok()).unwrap_or(1);
+    let mut tokens = Vec::new();
+
+    for (i, ply) in game.plies().iter().enumerate() {
+        if pos.stm() == Side::WHITE {
+            tokens.push(format!("{fullmove}."));
+        } else if i == 0 {
+            tokens.push(format!("{fullmove}..."));
+        }
+
+        tokens.push(san::to_san(&pos, &castling, ply.best_move));
+
+        let mut cp = T::centipawns(ply.score);
+
+        if pos.stm() == Side::BLACK {
+            cp = -cp;
+        }
+
+        // whole centipawns, and `+ 0.0` turns `-0.0` into `0.0`, so that scores
+        // just below zero are not written as `-0.00`
+        let mut comment = format!("{{[%eval {:.2}]", cp.round() / 100.0 + 0.0);
+
+        if let Some(visits) = ply.visits {
+            let total = visits.iter().map(|&(_, count)| u64::from(count)).sum::<u64>().max(1);
+
+            let mut sorted = visits.to_vec();
+            sorted.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+
+            for &(mov, count) in sorted.iter().take(options.alternatives) {
+                let pct = 100.0 * count as f64 / total as f64;
+                comment.push_str(&format!(" {} {pct:.0}%", san::to_san(&pos, &castling, mov)));
+            }
+        }
+
+        comment.push('}');
+        tokens.push(comment);
+
+        if pos.stm() == Side::BLACK {
+            fullmove += 1;
+        }
+
+        pos.make(ply.best_move, &castling);
+    }
+
+    tokens.push(result.to_string());
+
+    let mut line = String::new();
+    for token in tokens {
+        if !line.is_empty() && line.len() + 1 + token.len() > LINE_WIDTH {
+            writeln!(writer, "{line}")?;
+            line.clear();
+        }
+
+        if !line.is_empty() {
+            line.push(' ');
+        }
+
+        line.push_str(&token);
+    }
+
+    writeln!(writer, "{line}")?;
+    writeln!(writer)
+}
+
+/// A game as written in a PGN file, before any moves are checked.
+#[derive(Default)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    /// Moves in SAN, each with the text of the comments that follow it.
+    pub moves: Vec<(String, Option<String>)>,
+    pub result: Option<String>,
+    /// Line the game starts on, for error reports.
+    pub line: usize,
+}
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+}
+
+/// Splits a PGN file into games. Variations, NAGs and move numbers are
+/// dropped, comments are kept with the move before them.
+pub struct PgnReader<R> {
+    reader: R,
+    line: usize,
+    pending: Option<String>,
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader, line: 0, pending: None }
+    }
+
+    fn next_line(&mut self) -> io::Result<Option<String>> {
+        if let Some(line) = self.pending.take() {
+            return Ok(Some(line));
+        }
+
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        self.line += 1;
+        Ok(Some(line))
+    }
+
+    pub fn next_game(&mut self) -> io::Result<Option<PgnGame>> {
+        let mut game = PgnGame::default();
+        let mut movetext = String::new();
+        let mut started = false;
+        let mut in_comment = false;
+
+        while let Some(line) = self.next_line()? {
+            let trimmed = line.trim();
+
+            // a line of a `{...}` comment may start with a command such as `[%eval 0.31]`
+            let tag = trimmed
+                .strip_prefix('[')
+                .filter(|tag| !in_comment && tag.starts_with(|c: char| c.is_ascii_alphabetic()));
+
+            if let Some(tag) = tag {
+                if !movetext.trim().is_empty() {
+                    self.pending = Some(line);
+                    break;
+                }
+
+                if !started {
+                    game.line = self.line;
+                    started = true;
+                }
+
+                if let Some((key, value)) = tag.trim_end_matches(']').split_once(' ') {
+                    game.tags.push((key.to_string(), value.trim().trim_matches('"').to_string()));
+                }
+            } else if in_comment || !trimmed.starts_with('%') {
+                if !started && !trimmed.is_empty() {
+                    game.line = self.line;
+                    started = true;
+                }
+
+                movetext.push_str(&line);
+                movetext.push('\n');
+                in_comment = ends_in_comment(trimmed, in_comment);
+
+                if !in_comment && ["1-0", "0-1", "1/2-1/2", "*"].iter().any(|result| trimmed.ends_with(result)) {
+                    break;
+                }
+            }
+        }
+
+        if !started {
+            return Ok(None);
+        }
+
+        parse_movetext(&movetext, &mut game);
+        Ok(Some(game))
+    }
+}
+
+/// Whether a `{...}` comment is still open after a line of movetext.
+fn ends_in_comment(line: &str, mut in_comment: bool) -> bool {
+    for c in line.chars() {
+        match c {
+            '{' if !in_comment => in_comment = true,
+            '}' if in_comment => in_comment = false,
+            ';' if !in_comment => break,
+            _ => {}
+        }
+    }
+
+    in_comment
+}
+
+fn parse_movetext(text: &str, game: &mut PgnGame) {
+    let mut chars = text.chars().peekable();
+    let mut depth = 0;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                let comment = chars.by_ref().take_while(|&c| c != '}').collect::<String>();
+                let comment = comment.split_whitespace().collect::<Vec<_>>().join(" ");
+
+                if depth == 0 {
+                    if let Some((_, existing)) = game.moves.last_mut() {
+                        match existing {
+                            Some(existing) => {
+                                existing.push(' ');
+                                existing.push_str(&comment);
+                            }
+                            None => *existing = Some(comment),
+                        }
+                    }
+                }
+            }
+            ';' => {
+                chars.by_ref().take_while(|&c| c != '\n').for_each(drop);
+            }
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            c if c.is_whitespace() => {}
+            c => {
+                let mut token = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || "{;()".contains(next) {
+                        break;
+                    }
+
+                    token.push(next);
+                    chars.next();
+                }
+
+                if depth > 0 || token.starts_with('$') {
+                    continue;
+                }
+
+                if ["1-0", "0-1", "1/2-1/2", "*"].contains(&token.as_str()) {
+                    game.result = Some(token);
+                    continue;
+                }
+
+                // move numbers, possibly glued to the move as in `1.e4`
+                let token = token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+                if !token.is_empty() {
+                    game.moves.push((token.to_string(), None));
+                }
+            }
+        }
+    }
+}
+
+/// White-relative centipawns of a `[%eval ...]` command in a comment, with
+/// mates given as `mate_score` minus the distance.
+pub fn parse_eval(comment: &str, mate_score: f32) -> Option<f32> {
+    let start = comment.find("[%eval ")? + "[%eval ".len();
+    let value = comment[start..].split([']', ',', ' ']).next()?;
+
+    match value.strip_prefix('#') {
+        Some(mate) => {
+            let plies = mate.parse::<i32>().ok()?;
+            let score = mate_score - plies.abs() as f32;
+            Some(if mate.starts_with('-') { -score } else { score })
+        }
+        None => value.parse::<f32>().ok().map(|pawns| 100.0 * pawns),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use montyformat::{MontyFormat, MontyValueFormat};
+
+    use super::*;
+    use crate::testing;
+
+    const OPTIONS: Options = Options { games: 0..u64::MAX, alternatives: 2, event: "test" };
+
+    fn pgn<T: GameFormat>(game: &T) -> String {
+        let mut output = Vec::new();
+        write_pgn(game, 0, &mut output, &OPTIONS).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    fn games(text: &str) -> Vec<PgnGame> {
+        let mut reader = PgnReader::new(text.as_bytes());
+        std::iter::from_fn(|| reader.next_game().unwrap()).collect()
+    }
+
+    fn sans(game: &PgnGame) -> Vec<&str> {
+        game.moves.iter().map(|(san, _)| san.as_str()).collect()
+    }
+
+    #[test]
+    fn writes_value_games() {
+        let game = testing::value_game(STARTPOS, &["e4", "e5", "Nf3"], 1.0);
+
+        assert_eq!(
+            pgn(&game),
+            "[Event \"test\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"1\"]\n[White \"?\"]\n[Black \"?\"]\n\
+             [Result \"1-0\"]\n\n1. e4 {[%eval 0.00]} e5 {[%eval -0.10]} 2. Nf3 {[%eval 0.20]} 1-0\n\n"
+        );
+    }
+
+    #[test]
+    fn writes_the_start_position_and_black_first_moves() {
+        let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1";
+        let text = pgn(&testing::value_game(fen, &["c5", "Nf3"], 0.0));
+
+        assert!(text.contains(&format!("[SetUp \"1\"]\n[FEN \"{fen}\"]\n")), "{text}");
+        assert!(text.ends_with("\n1... c5 {[%eval 0.00]} 2. Nf3 {[%eval 0.10]} 0-1\n\n"), "{text}");
+    }
+
+    #[test]
+    fn writes_chess960_games_with_shredder_fen() {
+        let fen = "1r2k1r1/pppppppp/8/8/8/8/PPPPPPPP/1R2K1R1 w GBgb - 0 1";
+        let game = testing::value_game(fen, &["O-O", "O-O-O"], 0.5);
+
+        // as a binpack stores it, which does not keep the Chess960 flag
+        let game = MontyValueFormat::decode(&testing::binpack(&[game])).unwrap();
+        let text = pgn(&game);
+
+        assert!(text.contains(
+            "[Variant \"Chess960\"]\n[SetUp \"1\"]\n[FEN \"1r2k1r1/pppppppp/8/8/8/8/PPPPPPPP/1R2K1R1 w GBgb - 0 1\"]"
+        ));
+        assert!(text.ends_with("\n1. O-O {[%eval 0.00]} O-O-O {[%eval -0.10]} 1/2-1/2\n\n"), "{text}");
+
+        // and reads back to the same moves
+        let read = &games(&text)[0];
+        let (mut pos, castling) = fen::parse(read.tag("FEN").unwrap()).unwrap();
+
+        for ((san, _), data) in read.moves.iter().zip(&game.moves) {
+            let mov = san::parse_move(&pos, &castling, san).unwrap();
+            assert_eq!(mov, data.best_move);
+            pos.make(mov, &castling);
+        }
+    }
+
+    #[test]
+    fn standard_castling_is_not_chess960() {
+        let game = testing::value_game("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", &["O-O", "O-O-O"], 0.5);
+        let text = pgn(&game);
+
+        assert!(!text.contains("Variant"), "{text}");
+        assert!(text.contains("[FEN \"r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1\"]"), "{text}");
+        assert!(text.contains("1. O-O {[%eval 0.00]} O-O-O {[%eval -0.10]}"), "{text}");
+    }
+
+    #[test]
+    fn lists_the_most_visited_moves() {
+        let game = testing::policy_game(STARTPOS, &["e4"], 1.0);
+        let text = pgn::<MontyFormat>(&game);
+
+        // ties keep the order of the distribution
+        assert!(text.ends_with("\n1. e4 {[%eval 0.00] e4 100% Na3 0%} 1-0\n\n"), "{text}");
+    }
+
+    #[test]
+    fn parses_evals() {
+        let eval = |comment| parse_eval(comment, 3000.0).map(f32::round);
+
+        assert_eq!(eval("[%eval 0.31]"), Some(31.0));
+        assert_eq!(eval("+0.3/20 [%eval -1.50,24] [%clk 0:01:00]"), Some(-150.0));
+        assert_eq!(eval("[%eval #3]"), Some(2997.0));
+        assert_eq!(eval("[%eval #-2]"), Some(-2998.0));
+        assert_eq!(eval("[%clk 0:01:00]"), None);
+        assert_eq!(eval("[%eval ?]"), None);
+    }
+
+    #[test]
+    fn parses_movetext() {
+        let mut game = PgnGame::default();
+        let text = "1.e4 {first} {second} e5 (1... c5 {variation} 2. Nf3) 2. Nf3 $1 Nc6 ; to the end of the line\n\
+                    3. Bb5!? a6 1/2-1/2";
+        parse_movetext(text, &mut game);
+
+        assert_eq!(sans(&game), ["e4", "e5", "Nf3", "Nc6", "Bb5!?", "a6"]);
+        assert_eq!(game.moves[0].1.as_deref(), Some("first second"));
+        assert_eq!(game.moves[1].1, None);
+        assert_eq!(game.result.as_deref(), Some("1/2-1/2"));
+    }
+
+    #[test]
+    fn reads_games_with_comment_lines() {
+        let text =
+            "[Event \"a\"]\n[Result \"1-0\"]\n\n1. e4 {\n[%eval 0.31]\n} e5 {[%eval 0.2]\n[%clk 0:01:00]} 1-0\n\n\
+                    [Event \"b\"]\n\n1. d4 *\n\n\
+                    1. c4\n[Event \"c\"]\n\n1. Nf3 0-1\n";
+        let games = games(text);
+
+        assert_eq!(games.len(), 4);
+
+        assert_eq!(games[0].tag("Event"), Some("a"));
+        assert_eq!(sans(&games[0]), ["e4", "e5"]);
+        assert_eq!(games[0].moves[0].1.as_deref(), Some("[%eval 0.31]"));
+        assert_eq!(games[0].moves[1].1.as_deref(), Some("[%eval 0.2] [%clk 0:01:00]"));
+        assert_eq!(games[0].result.as_deref(), Some("1-0"));
+
+        assert_eq!((games[1].tag("Event"), games[1].line), (Some("b"), 9));
+        assert_eq!(sans(&games[1]), ["d4"]);
+
+        // a game without a result ends at the next tag
+        assert_eq!((games[2].tags.len(), games[2].line), (0, 13));
+        assert_eq!(sans(&games[2]), ["c4"]);
+        assert_eq!((games[3].tag("Event"), sans(&games[3])), (Some("c"), vec!["Nf3"]));
+    }
+}
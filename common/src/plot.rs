@@ -0,0 +1,180 @@
+use std::{fmt::Write as _, io::Write};
+
+use crate::{
+    cli::{self, Cli},
+    runlog::{self, Entry},
+};
+
+const WIDTH: f64 = 900.0;
+const HEIGHT: f64 = 540.0;
+const MARGIN: (f64, f64, f64, f64) = (70.0, 20.0, 30.0, 50.0); // left, right, top, bottom
+const TICKS: usize = 5;
+const COLOURS: [&str; 8] = ["#1f77b4", "#d62728", "#2ca02c", "#ff7f0e", "#9467bd", "#8c564b", "#e377c2", "#17becf"];
+
+/// Entry point for the `plot-log` binaries.
+pub fn main(name: &'static str, about: &'static str) {
+    let args = Cli::new(name, about)
+        .multiple("log", "log.csv, or the checkpoint directory containing it, one series per run")
+        .required("output", "SVG file to write")
+        .default("metrics", "loss,validation_loss", "Comma separated columns to plot")
+        .flag("log-scale", "Use a logarithmic y axis")
+        .parse();
+
+    let logs = args.get_all("log");
+    if logs.is_empty() {
+        cli::fail("no logs given, use --log");
+    }
+
+    let metrics = args.get("metrics").split(',').map(str::trim).collect::<Vec<_>>();
+    for metric in &metrics {
+        if metric_of(metric).is_none() {
+            cli::fail(format!("unknown metric '{metric}'"));
+        }
+    }
+
+    let mut series = Vec::new();
+    for path in logs {
+        let entries = runlog::read(path).unwrap_or_else(|err| cli::fail(format!("could not read '{path}': {err}")));
+
+        for metric in &metrics {
+            let points = points(&entries, metric_of(metric).unwrap());
+
+            if !points.is_empty() {
+                let label = if logs.len() > 1 { format!("{path} {metric}") } else { metric.to_string() };
+                series.push((label, points));
+            }
+        }
+    }
+
+    if series.is_empty() {
+        cli::fail("nothing to plot, the logs have no values for these metrics");
+    }
+
+    let svg = render(&series, args.flag("log-scale"), &metrics.join(", "));
+    let out_path = args.get("output");
+
+    std::fs::File::create(out_path)
+        .and_then(|mut file| file.write_all(svg.as_bytes()))
+        .unwrap_or_else(|err| cli::fail(format!("could not write '{out_path}': {err}")));
+
+    println!("Written {out_path} ({} series)", series.len());
+}
+
+fn metric_of(name: &str) -> Option<fn(&Entry) -> Option<f64>> {
+    Some(match name {
+        "loss" => |entry| entry.loss.map(f64::from),
+        "lr" => |entry| Some(f64::from(entry.lr)),
+        "positions_per_sec" => |entry| entry.positions_per_sec,
+        "validation_loss" => |entry| entry.validation_loss.map(f64::from),
+        "validation_accuracy" => |entry| entry.validation_accuracy.map(f64::from),
+        _ => return None,
+    })
+}
+
+/// Points at fractional superbatches, taking the largest batch index in the
+/// log as the length of a superbatch.
+fn points(entries: &[Entry], metric: fn(&Entry) -> Option<f64>) -> Vec<(f64, f64)> {
+    let batches = entries.iter().map(|entry| entry.batch).max().unwrap_or(1).max(1) as f64;
+
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let x = entry.superbatch.saturating_sub(1) as f64 + entry.batch as f64 / batches;
+            metric(entry).filter(|y| y.is_finite()).map(|y| (x, y))
+        })
+        .collect()
+}
+
+fn render(series: &[(String, Vec<(f64, f64)>)], log_scale: bool, title: &str) -> String {
+    let all = series.iter().flat_map(|(_, points)| points.iter());
+    let (mut x_min, mut x_max, mut y_min, mut y_max) = (f64::MAX, f64::MIN, f64::MAX, f64::MIN);
+
+    for &(x, y) in all {
+        if log_scale && y <= 0.0 {
+            continue;
+        }
+
+        let y = if log_scale { y.log10() } else { y };
+        (x_min, x_max, y_min, y_max) = (x_min.min(x), x_max.max(x), y_min.min(y), y_max.max(y));
+    }
+
+    if x_max <= x_min {
+        x_max = x_min + 1.0;
+    }
+
+    if y_max <= y_min {
+        y_max = y_min + 1.0;
+    }
+
+    let (left, right, top, bottom) = MARGIN;
+    let (plot_w, plot_h) = (WIDTH - left - right, HEIGHT - top - bottom);
+    let sx = |x: f64| left + (x - x_min) / (x_max - x_min) * plot_w;
+    let sy = |y: f64| top + (1.0 - (y - y_min) / (y_max - y_min)) * plot_h;
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{HEIGHT}" font-family="sans-serif" font-size="12">"#
+    );
+    let _ = writeln!(svg, r#"<rect width="100%" height="100%" fill="white"/>"#);
+    let _ = writeln!(svg, r#"<text x="{}" y="18" text-anchor="middle">{}</text>"#, WIDTH / 2.0, escape(title));
+
+    for i in 0..=TICKS {
+        let t = i as f64 / TICKS as f64;
+        let (x, y) = (x_min + t * (x_max - x_min), y_min + t * (y_max - y_min));
+        let label = if log_scale { format!("{:.3e}", 10f64.powf(y)) } else { format!("{y:.4}") };
+
+        let _ = writeln!(
+            svg,
+            r##"<line x1="{left}" x2="{}" y1="{py:.1}" y2="{py:.1}" stroke="#ddd"/><text x="{}" y="{:.1}" text-anchor="end">{label}</text>"##,
+            WIDTH - right,
+            left - 6.0,
+            sy(y) + 4.0,
+            py = sy(y)
+        );
+        let _ = writeln!(
+            svg,
+            r##"<line x1="{px:.1}" x2="{px:.1}" y1="{top}" y2="{}" stroke="#ddd"/><text x="{px:.1}" y="{}" text-anchor="middle">{x:.0}</text>"##,
+            HEIGHT - bottom,
+            HEIGHT - bottom + 16.0,
+            px = sx(x)
+        );
+    }
+
+    let _ = writeln!(
+        svg,
+        r#"<rect x="{left}" y="{top}" width="{plot_w}" height="{plot_h}" fill="none" stroke="black"/><text x="{}" y="{}" text-anchor="middle">superbatch</text>"#,
+        left + plot_w / 2.0,
+        HEIGHT - 12.0
+    );
+
+    for (i, (label, points)) in series.iter().enumerate() {
+        let colour = COLOURS[i % COLOURS.len()];
+        let path = points
+            .iter()
+            .filter(|&&(_, y)| !log_scale || y > 0.0)
+            .map(|&(x, y)| format!("{:.1},{:.1}", sx(x), sy(if log_scale { y.log10() } else { y })))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let _ = writeln!(svg, r#"<polyline points="{path}" fill="none" stroke="{colour}" stroke-width="1.5"/>"#);
+
+        let ly = top + 16.0 + 16.0 * i as f64;
+        let _ = writeln!(
+            svg,
+            r#"<line x1="{}" x2="{}" y1="{ly}" y2="{ly}" stroke="{colour}" stroke-width="2"/><text x="{}" y="{}" text-anchor="end">{}</text>"#,
+            WIDTH - right - 30.0,
+            WIDTH - right - 10.0,
+            WIDTH - right - 36.0,
+            ly + 4.0,
+            escape(label)
+        );
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
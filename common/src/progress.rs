@@ -0,0 +1,88 @@
+use std::io::Write;
+
+/// Prints progress every time `interval` more units have been processed.
+pub struct Progress {
+    label: &'static str,
+    unit: &'static str,
+    total: Option<u64>,
+    interval: u64,
+    last: u64,
+}
+
+impl Progress {
+    /// Prints `{label} {done} {unit}` on its own line.
+    pub fn counter(label: &'static str, unit: &'static str, interval: u64) -> Self {
+        Self { label, unit, total: None, interval, last: 0 }
+    }
+
+    /// Prints `{label} {done}/{total} {unit} (xx.xx%)`, overwriting the line.
+    pub fn fraction(label: &'static str, unit: &'static str, total: u64, interval: u64) -> Self {
+        Self { label, unit, total: Some(total), interval, last: 0 }
+    }
+
+    pub fn update(&mut self, done: u64) {
+        if self.due(done) {
+            self.print(done);
+        }
+
+        self.last = done;
+    }
+
+    pub fn finish(&mut self) {
+        self.print(self.last);
+
+        if self.total.is_some() {
+            println!();
+        }
+    }
+
+    /// Whether another `interval` units have been passed since the last update.
+    fn due(&self, done: u64) -> bool {
+        done / self.interval > self.last / self.interval
+    }
+
+    fn message(&self, done: u64) -> String {
+        let (label, unit) = (self.label, self.unit);
+
+        if let Some(total) = self.total {
+            let pct = done as f64 / total.max(1) as f64 * 100.0;
+            format!("{label} {done}/{total} {unit} ({pct:.2}%)")
+        } else {
+            format!("{label} {done} {unit}")
+        }
+    }
+
+    fn print(&self, done: u64) {
+        if self.total.is_some() {
+            print!("{}\r", self.message(done));
+            let _ = std::io::stdout().flush();
+        } else {
+            println!("{}", self.message(done));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn due_once_per_interval() {
+        let mut progress = Progress::counter("Read", "Games", 100);
+        let mut due = Vec::new();
+
+        for done in [10, 99, 100, 150, 199, 350, 351] {
+            due.push(progress.due(done));
+            progress.last = done;
+        }
+
+        assert_eq!(due, [false, false, true, false, false, true, false]);
+    }
+
+    #[test]
+    fn messages() {
+        assert_eq!(Progress::counter("Read", "Games", 1).message(42), "Read 42 Games");
+        assert_eq!(Progress::fraction("Read", "Bytes", 400, 1).message(100), "Read 100/400 Bytes (25.00%)");
+        assert_eq!(Progress::fraction("Read", "Bytes", 0, 1).message(0), "Read 0/0 Bytes (0.00%)");
+    }
+}
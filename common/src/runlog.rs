@@ -0,0 +1,162 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+    str::FromStr,
+    time::Instant,
+};
+
+/// Written by both trainers to their checkpoint directory.
+pub const FILE_NAME: &str = "log.csv";
+
+const HEADER: &str = "superbatch,batch,loss,lr,positions_per_sec,validation_loss,validation_accuracy";
+
+/// One row of the log. Metrics a trainer does not measure at that point are
+/// left empty in the file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Entry {
+    pub superbatch: usize,
+    pub batch: usize,
+    pub loss: Option<f32>,
+    pub lr: f32,
+    /// Only measured on training rows.
+    pub positions_per_sec: Option<f64>,
+    pub validation_loss: Option<f32>,
+    pub validation_accuracy: Option<f32>,
+}
+
+/// Appends entries to `log.csv`, flushing each so the file can be plotted
+/// while the run is still going.
+pub struct RunLog {
+    file: File,
+    since: Instant,
+}
+
+impl RunLog {
+    /// Appends to an existing log, so that resumed runs continue it.
+    pub fn open(dir: &str) -> io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+
+        let path = Path::new(dir).join(FILE_NAME);
+        let exists = path.is_file();
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+
+        if !exists {
+            writeln!(file, "{HEADER}")?;
+        }
+
+        Ok(Self { file, since: Instant::now() })
+    }
+
+    /// Positions per second since the previous call, or since opening.
+    pub fn speed(&mut self, positions: usize) -> f64 {
+        let rate = positions as f64 / self.since.elapsed().as_secs_f64().max(f64::EPSILON);
+        self.since = Instant::now();
+        rate
+    }
+
+    pub fn write(&mut self, entry: &Entry) -> io::Result<()> {
+        let opt = |x: Option<f32>| x.map(|x| x.to_string()).unwrap_or_default();
+        let speed = entry.positions_per_sec.map(|x| format!("{x:.0}")).unwrap_or_default();
+
+        writeln!(
+            self.file,
+            "{},{},{},{},{},{},{}",
+            entry.superbatch,
+            entry.batch,
+            opt(entry.loss),
+            entry.lr,
+            speed,
+            opt(entry.validation_loss),
+            opt(entry.validation_accuracy)
+        )?;
+
+        self.file.flush()
+    }
+}
+
+/// Reads a log written by [`RunLog`], skipping the header.
+pub fn read(path: &str) -> io::Result<Vec<Entry>> {
+    let path = Path::new(path);
+    let path = if path.is_dir() { path.join(FILE_NAME) } else { path.to_path_buf() };
+
+    let mut entries = Vec::new();
+
+    for (index, line) in BufReader::new(File::open(&path)?).lines().enumerate() {
+        let line = line?;
+
+        if index == 0 || line.trim().is_empty() {
+            continue;
+        }
+
+        let invalid =
+            || io::Error::new(io::ErrorKind::InvalidData, format!("{}:{}: {line}", path.display(), index + 1));
+        let fields = line.split(',').map(str::trim).collect::<Vec<_>>();
+
+        if fields.len() != HEADER.split(',').count() {
+            return Err(invalid());
+        }
+
+        entries.push(Entry {
+            superbatch: fields[0].parse().map_err(|_| invalid())?,
+            batch: fields[1].parse().map_err(|_| invalid())?,
+            loss: opt(fields[2]).map_err(|_| invalid())?,
+            lr: fields[3].parse().map_err(|_| invalid())?,
+            positions_per_sec: opt(fields[4]).map_err(|_| invalid())?,
+            validation_loss: opt(fields[5]).map_err(|_| invalid())?,
+            validation_accuracy: opt(fields[6]).map_err(|_| invalid())?,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// An empty field is a metric that was not measured.
+fn opt<T: FromStr>(field: &str) -> Result<Option<T>, T::Err> {
+    if field.is_empty() {
+        Ok(None)
+    } else {
+        field.parse().map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn reads_back_what_it_writes() {
+        let temp = TempDir::new("runlog");
+        let dir = temp.to_str();
+
+        let entries = [
+            Entry {
+                superbatch: 1,
+                batch: 64,
+                loss: Some(2.125),
+                lr: 0.001,
+                positions_per_sec: Some(1_500_000.0),
+                ..Default::default()
+            },
+            Entry {
+                superbatch: 40,
+                batch: 6104,
+                lr: 2.5e-7,
+                validation_loss: Some(1.7182817),
+                validation_accuracy: Some(0.4375),
+                ..Default::default()
+            },
+        ];
+
+        // reopening appends rather than writing a second header
+        for entry in &entries {
+            RunLog::open(dir).unwrap().write(entry).unwrap();
+        }
+
+        assert_eq!(read(dir).unwrap(), entries);
+
+        std::fs::write(temp.join(FILE_NAME), format!("{HEADER}\n1,64,x,0.001,0,,\n")).unwrap();
+        assert_eq!(read(dir).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
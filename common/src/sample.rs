@@ -0,0 +1,210 @@
+use std::io::BufRead;
+
+use montyformat::chess::{Castling, Move, Position};
+
+use crate::{
+    cli::{self, Cli, Matches},
+    fen,
+    format::GameFormat,
+    rng::Rand,
+};
+
+/// A position to evaluate, with the search data it was stored with when it
+/// was sampled from a binpack.
+pub struct Sample {
+    pub fen: String,
+    pub pos: Position,
+    pub castling: Castling,
+    pub stored: Option<Stored>,
+}
+
+impl Sample {
+    /// The stored search data as a JSON object, with the score in
+    /// side-to-move centipawns and visits as fractions of the total.
+    pub fn stored_json<T: GameFormat>(&self) -> Option<String> {
+        let stored = self.stored.as_ref()?;
+        let uci = |mov: Move| json_string(&mov.to_uci(&self.castling));
+
+        let mut json = format!(
+            "{{\"best_move\":{},\"cp\":{:.0},\"result\":{}",
+            uci(stored.best_move),
+            T::centipawns(stored.score),
+            stored.result
+        );
+
+        if let Some(visits) = &stored.visits {
+            let total = visits.iter().map(|&(_, count)| u64::from(count)).sum::<u64>().max(1);
+            let entries = visits
+                .iter()
+                .map(|&(mov, count)| format!("{{\"move\":{},\"p\":{:.6}}}", uci(mov), count as f64 / total as f64))
+                .collect::<Vec<_>>();
+
+            json.push_str(&format!(",\"visits\":[{}]", entries.join(",")));
+        }
+
+        json.push('}');
+        Some(json)
+    }
+}
+
+pub struct Stored {
+    pub best_move: Move,
+    /// As stored by the format, see [`crate::format::Ply`].
+    pub score: f32,
+    pub result: f32,
+    pub visits: Option<Vec<(Move, u32)>>,
+}
+
+/// Adds the options read by [`load`].
+pub fn options(cli: Cli) -> Cli {
+    cli.multiple("fen", "Position to evaluate")
+        .optional("fens", "File of positions to evaluate, one FEN or EPD per line")
+        .optional("binpack", "Binpack to sample positions from")
+        .default("samples", "1000", "Number of positions to sample from --binpack")
+        .default("seed", "0", "Seed for sampling from --binpack")
+}
+
+pub fn has_source(args: &Matches) -> bool {
+    !args.get_all("fen").is_empty() || args.get_opt("fens").is_some() || args.get_opt("binpack").is_some()
+}
+
+/// Positions from every source given, or `default` if there are none.
+pub fn load<T: GameFormat>(args: &Matches, default: &str) -> Vec<Sample> {
+    let mut samples = args
+        .get_all("fen")
+        .iter()
+        .map(|fen| from_fen(fen).unwrap_or_else(|err| cli::fail(format!("invalid FEN '{fen}': {err}"))))
+        .collect::<Vec<_>>();
+
+    if let Some(path) = args.get_opt("fens") {
+        samples.extend(from_fens(path));
+    }
+
+    if let Some(path) = args.get_opt("binpack") {
+        samples.extend(from_binpack::<T>(path, args.parse("samples"), args.parse("seed")));
+    }
+
+    if samples.is_empty() {
+        samples.push(from_fen(default).expect("Default position is valid!"));
+    }
+
+    samples
+}
+
+pub fn from_fen(fen: &str) -> Result<Sample, String> {
+    let (pos, castling) = fen::parse(fen)?;
+    Ok(Sample { fen: fen.to_string(), pos, castling, stored: None })
+}
+
+/// Reads a FEN from each line, ignoring anything after a `|` or `;` so that
+/// `export` output and EPD files work too. Invalid lines are reported and
+/// skipped.
+pub fn from_fens(path: &str) -> Vec<Sample> {
+    let mut samples = Vec::new();
+    let mut skipped = 0;
+
+    for (index, line) in cli::open_input(path).lines().enumerate() {
+        let line = line.unwrap_or_else(|err| cli::fail(format!("could not read '{path}': {err}")));
+        let fen = line.split(['|', ';']).next().unwrap_or("").trim();
+
+        if fen.is_empty() || fen.starts_with('#') {
+            continue;
+        }
+
+        let fields = fen.split_whitespace().collect::<Vec<_>>();
+        let fen = match fields.len() {
+            // EPD, possibly followed by operations
+            4.. if fields.get(4).is_none_or(|field| field.parse::<u32>().is_err()) => {
+                format!("{} 0 1", fields[..4].join(" "))
+            }
+            _ => fen.to_string(),
+        };
+
+        match from_fen(&fen) {
+            Ok(sample) => samples.push(sample),
+            Err(err) => {
+                eprintln!("{path}:{}: skipped, {err}", index + 1);
+                skipped += 1;
+            }
+        }
+    }
+
+    if skipped > 0 {
+        eprintln!("Skipped {skipped} invalid positions in '{path}'");
+    }
+
+    samples
+}
+
+/// Reservoir-samples `count` positions from every game of the binpack.
+pub fn from_binpack<T: GameFormat>(path: &str, count: usize, seed: u64) -> Vec<Sample> {
+    let mut reader = cli::open_input(path);
+    let mut rng = Rand::new(seed);
+    let mut buffer = Vec::new();
+    let mut seen = 0u64;
+    let mut samples = Vec::with_capacity(count);
+
+    loop {
+        match reader.fill_buf() {
+            Ok([]) => break,
+            Ok(_) => {}
+            Err(err) => cli::fail(format!("could not read '{path}': {err}")),
+        }
+
+        let game = T::deserialise_fast_into_buffer(&mut reader, &mut buffer)
+            .and_then(|_| T::decode(&buffer))
+            .unwrap_or_else(|err| cli::fail(format!("could not read '{path}': {err}")));
+
+        let castling = game.castling();
+        let mut pos = game.startpos();
+
+        for ply in game.plies() {
+            seen += 1;
+
+            let slot = if samples.len() < count {
+                Some(samples.len())
+            } else {
+                Some((rng.rand() % seen) as usize).filter(|&slot| slot < count)
+            };
+
+            if let Some(slot) = slot {
+                let stored = Stored {
+                    best_move: ply.best_move,
+                    score: ply.score,
+                    result: game.result(),
+                    visits: ply.visits.map(<[_]>::to_vec),
+                };
+
+                let sample = Sample { fen: pos.as_fen(), pos, castling, stored: Some(stored) };
+
+                if slot == samples.len() {
+                    samples.push(sample);
+                } else {
+                    samples[slot] = sample;
+                }
+            }
+
+            pos.make(ply.best_move, &castling);
+        }
+    }
+
+    samples
+}
+
+/// Quotes and escapes a string for JSON output.
+pub fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+    out
+}
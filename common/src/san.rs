@@ -0,0 +1,221 @@
+use montyformat::chess::{Castling, Flag, Move, Piece, Position};
+
+const PIECE_LETTERS: [char; 8] = [' ', ' ', 'P', 'N', 'B', 'R', 'Q', 'K'];
+
+pub fn square_name(sq: usize) -> String {
+    format!("{}{}", (b'a' + (sq % 8) as u8) as char, sq / 8 + 1)
+}
+
+fn piece_on(pos: &Position, sq: usize) -> usize {
+    let bbs = pos.bbs();
+    (Piece::PAWN..=Piece::KING).find(|&piece| bbs[piece] & (1 << sq) > 0).unwrap_or(0)
+}
+
+/// Standard algebraic notation of a legal move, including check and mate
+/// suffixes. Castling is always `O-O`/`O-O-O`, which also covers Chess960.
+pub fn to_san(pos: &Position, castling: &Castling, mov: Move) -> String {
+    let mut san = san_without_suffix(pos, castling, mov);
+
+    let mut next = *pos;
+    next.make(mov, castling);
+
+    if next.in_check() {
+        let mut has_moves = false;
+        next.map_legal_moves(castling, |_| has_moves = true);
+        san.push(if has_moves { '+' } else { '#' });
+    }
+
+    san
+}
+
+fn san_without_suffix(pos: &Position, castling: &Castling, mov: Move) -> String {
+    match mov.flag() {
+        Flag::KS => return "O-O".to_string(),
+        Flag::QS => return "O-O-O".to_string(),
+        _ => {}
+    }
+
+    let src = usize::from(mov.src());
+    let to = usize::from(mov.to());
+    let piece = piece_on(pos, src);
+
+    let mut san = String::new();
+
+    if piece == Piece::PAWN {
+        if mov.is_capture() {
+            san.push((b'a' + (src % 8) as u8) as char);
+            san.push('x');
+        }
+
+        san.push_str(&square_name(to));
+
+        if mov.is_promo() {
+            san.push('=');
+            san.push(PIECE_LETTERS[mov.promo_pc()]);
+        }
+
+        return san;
+    }
+
+    san.push(PIECE_LETTERS[piece]);
+
+    // other pieces of the same kind that can also reach the target square
+    let mut rivals = Vec::new();
+    pos.map_legal_moves(castling, |other| {
+        let other_src = usize::from(other.src());
+        let castles = matches!(other.flag(), Flag::KS | Flag::QS);
+
+        if !castles && other_src != src && usize::from(other.to()) == to && piece_on(pos, other_src) == piece {
+            rivals.push(other_src);
+        }
+    });
+
+    if !rivals.is_empty() {
+        let file = (b'a' + (src % 8) as u8) as char;
+        let rank = (b'1' + (src / 8) as u8) as char;
+
+        if rivals.iter().all(|&other| other % 8 != src % 8) {
+            san.push(file);
+        } else if rivals.iter().all(|&other| other / 8 != src / 8) {
+            san.push(rank);
+        } else {
+            san.push(file);
+            san.push(rank);
+        }
+    }
+
+    if mov.is_capture() {
+        san.push('x');
+    }
+
+    san.push_str(&square_name(to));
+    san
+}
+
+/// Finds the legal move written as `san`, tolerating check and annotation
+/// suffixes, `0-0` style castling and promotions without `=`.
+pub fn from_san(pos: &Position, castling: &Castling, san: &str) -> Option<Move> {
+    let wanted = normalise(san);
+    let mut found = None;
+
+    pos.map_legal_moves(castling, |mov| {
+        if found.is_none() && normalise(&san_without_suffix(pos, castling, mov)) == wanted {
+            found = Some(mov);
+        }
+    });
+
+    if found.is_none() {
+        // over-disambiguated moves such as `Ng1f3`
+        pos.map_legal_moves(castling, |mov| {
+            let piece = PIECE_LETTERS[piece_on(pos, usize::from(mov.src()))];
+            let full = format!("{piece}{}{}", square_name(usize::from(mov.src())), square_name(usize::from(mov.to())));
+
+            if found.is_none() && full == wanted.replace('x', "") {
+                found = Some(mov);
+            }
+        });
+    }
+
+    found
+}
+
+/// Accepts SAN or UCI.
+pub fn parse_move(pos: &Position, castling: &Castling, text: &str) -> Option<Move> {
+    from_san(pos, castling, text).or_else(|| {
+        let mut found = None;
+        pos.map_legal_moves(castling, |mov| {
+            if mov.to_uci(castling) == text {
+                found = Some(mov);
+            }
+        });
+        found
+    })
+}
+
+fn normalise(san: &str) -> String {
+    let san = san.trim_end_matches(['+', '#', '!', '?']).replace('0', "O").replace('=', "");
+    san.strip_suffix("ep").or_else(|| san.strip_suffix("e.p.")).unwrap_or(&san).trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen;
+
+    /// SAN of the legal move `uci` in `fen`.
+    fn san(fen: &str, uci: &str) -> String {
+        let (pos, castling) = fen::parse(fen).unwrap();
+        let mov = parse_move(&pos, &castling, uci).unwrap_or_else(|| panic!("{uci} is not legal in {fen}"));
+        to_san(&pos, &castling, mov)
+    }
+
+    /// UCI of the move written `text` in `fen`, if it is legal.
+    fn uci(fen: &str, text: &str) -> Option<String> {
+        let (pos, castling) = fen::parse(fen).unwrap();
+        parse_move(&pos, &castling, text).map(|mov| mov.to_uci(&castling))
+    }
+
+    const STARTPOS: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    #[test]
+    fn writes_piece_and_pawn_moves() {
+        assert_eq!(san(STARTPOS, "e2e4"), "e4");
+        assert_eq!(san(STARTPOS, "g1f3"), "Nf3");
+        assert_eq!(san("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2", "e4d5"), "exd5");
+        assert_eq!(san("rnbqkbnr/ppp2ppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3", "e5d6"), "exd6");
+    }
+
+    #[test]
+    fn disambiguates_by_file_then_rank_then_square() {
+        assert_eq!(san("6k1/8/8/8/8/8/4K3/R6R w - - 0 1", "a1d1"), "Rad1");
+        assert_eq!(san("3k4/8/8/R7/8/8/4K3/R7 w - - 0 1", "a1a3"), "R1a3");
+        assert_eq!(san("3k4/8/8/R7/8/8/4K3/R7 w - - 0 1", "a5a3"), "R5a3");
+        assert_eq!(san("4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1", "a1b2"), "Qa1b2");
+
+        // a pinned rival does not count
+        assert_eq!(san("k3r3/8/8/8/8/8/4N3/2N1K3 w - - 0 1", "c1d3"), "Nd3");
+    }
+
+    #[test]
+    fn writes_promotions_checks_and_mates() {
+        assert_eq!(san("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8q"), "b8=Q+");
+        assert_eq!(san("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8n"), "b8=N");
+        assert_eq!(san("2n1k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7c8r"), "bxc8=R+");
+        assert_eq!(san("7k/8/6K1/8/8/8/8/R7 w - - 0 1", "a1a8"), "Ra8#");
+    }
+
+    #[test]
+    fn writes_castling() {
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_eq!(san(fen, "e1g1"), "O-O");
+        assert_eq!(san(fen, "e1c1"), "O-O-O");
+
+        // Chess960 castling is written as the king taking its own rook
+        let fen = "1r2k1r1/pppppppp/8/8/8/8/PPPPPPPP/1R2K1R1 w GBgb - 0 1";
+        assert_eq!(uci(fen, "O-O").as_deref(), Some("e1g1"));
+        assert_eq!(uci(fen, "O-O-O").as_deref(), Some("e1b1"));
+        assert_eq!(san(fen, "e1b1"), "O-O-O");
+    }
+
+    #[test]
+    fn reads_loose_san() {
+        assert_eq!(uci(STARTPOS, "Nf3!?").as_deref(), Some("g1f3"));
+        assert_eq!(uci(STARTPOS, "Ng1f3").as_deref(), Some("g1f3"));
+        assert_eq!(uci("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", "0-0-0").as_deref(), Some("e8c8"));
+        assert_eq!(uci("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b8Q+").as_deref(), Some("b7b8q"));
+        assert_eq!(
+            uci("rnbqkbnr/ppp2ppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3", "exd6e.p.").as_deref(),
+            Some("e5d6")
+        );
+    }
+
+    #[test]
+    fn reads_uci_and_rejects_illegal_moves() {
+        assert_eq!(uci(STARTPOS, "e2e4").as_deref(), Some("e2e4"));
+        assert_eq!(uci("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8r").as_deref(), Some("b7b8r"));
+
+        for text in ["e5", "Nf6", "O-O", "e2e5", "b1d2", ""] {
+            assert_eq!(uci(STARTPOS, text), None, "{text}");
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::path::{Path, PathBuf};
+
+use montyformat::{MontyFormat, MontyValueFormat, SearchData, SearchResult};
+
+use crate::{fen, format::GameFormat, san};
+
+pub const STARTPOS: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// A scratch directory for one test, removed again when dropped so that
+/// test runs do not accumulate directories in the system temp dir.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("montytrain-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn join(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.0.join(path)
+    }
+
+    pub fn to_str(&self) -> &str {
+        self.0.to_str().unwrap()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A `MontyValueFormat` game from `fen` playing `moves`, given in SAN or UCI,
+/// each scored `ply * 10` centipawns so that plies can be told apart.
+pub fn value_game(fen: &str, moves: &[&str], result: f32) -> MontyValueFormat {
+    let (startpos, castling) = fen::parse(fen).unwrap();
+    let mut game = MontyValueFormat { startpos, castling, result, moves: Vec::new() };
+    let mut pos = startpos;
+
+    for (ply, text) in moves.iter().enumerate() {
+        let best_move = san::parse_move(&pos, &castling, text).unwrap_or_else(|| panic!("illegal move {text}"));
+        pos.make(best_move, &castling);
+        game.moves.push(SearchResult { best_move, score: 10 * ply as i16 });
+    }
+
+    game
+}
+
+/// A `MontyFormat` game from `fen` playing `moves`, given in SAN or UCI, each
+/// scored 0.5 with every visit on the move played. The distribution lists all
+/// legal moves, sorted as the format stores them.
+pub fn policy_game(fen: &str, moves: &[&str], result: f32) -> MontyFormat {
+    let (startpos, castling) = fen::parse(fen).unwrap();
+    let mut game = MontyFormat::new(startpos, castling);
+    game.result = result;
+    let mut pos = startpos;
+
+    for text in moves {
+        let best_move = san::parse_move(&pos, &castling, text).unwrap_or_else(|| panic!("illegal move {text}"));
+        let mut dist = Vec::new();
+        pos.map_legal_moves(&castling, |mov| dist.push((mov, if mov == best_move { 100 } else { 0 })));
+        dist.sort_by_key(|&(mov, _)| u16::from(mov));
+
+        pos.make(best_move, &castling);
+        game.push(SearchData { best_move, score: 0.5, visit_distribution: Some(dist) });
+    }
+
+    game
+}
+
+/// The games back to back, as they are laid out in a binpack.
+pub fn binpack<T: GameFormat>(games: &[T]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+
+    for game in games {
+        game.encode(&mut bytes).unwrap();
+    }
+
+    bytes
+}
+
+/// Every game of a `MontyValueFormat` binpack.
+pub fn read_value_games(mut bytes: &[u8]) -> Vec<MontyValueFormat> {
+    let mut games = Vec::new();
+
+    while !bytes.is_empty() {
+        games.push(MontyValueFormat::deserialise_from(&mut bytes, Vec::new()).unwrap());
+    }
+
+    games
+}
@@ -0,0 +1,317 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, BufRead, Read},
+};
+
+use montyformat::chess::{Move, Piece, Position, Side};
+
+use crate::{
+    cli::{self, Cli},
+    format::GameFormat,
+    progress::Progress,
+};
+
+/// Problems found in a file, counted by kind.
+#[derive(Default)]
+pub struct Report {
+    pub records: u64,
+    pub bytes: u64,
+    problems: BTreeMap<&'static str, u64>,
+    first: Option<(u64, String)>,
+}
+
+impl Report {
+    /// Records a problem in the record starting at byte `offset`.
+    pub fn problem(&mut self, offset: u64, kind: &'static str, detail: impl FnOnce() -> String) {
+        *self.problems.entry(kind).or_default() += 1;
+
+        if self.first.is_none() {
+            self.first = Some((offset, format!("{kind}: {}", detail())));
+        }
+    }
+
+    pub fn total_problems(&self) -> u64 {
+        self.problems.values().sum()
+    }
+
+    pub fn print(&self, unit: &str) {
+        println!("Records : {} {unit}", self.records);
+        println!("Bytes   : {}", self.bytes);
+        println!("Problems: {}", self.total_problems());
+
+        for (kind, count) in &self.problems {
+            println!(" - {kind}: {count}");
+        }
+
+        if let Some((offset, detail)) = &self.first {
+            println!("First bad record at byte {offset}:");
+            println!("  {detail}");
+        }
+    }
+
+    /// Prints the report and exits with a non-zero code if anything was wrong.
+    pub fn finish(&self, unit: &str) {
+        self.print(unit);
+
+        if self.total_problems() > 0 {
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Entry point for the `verify` binaries of each game format.
+pub fn main<T: GameFormat>(name: &'static str, about: &'static str) {
+    let args = Cli::new(name, about).required("input", "Binpack to verify").parse();
+
+    let path = args.get("input");
+    println!("Verifying {path:#?} as {}", T::NAME);
+
+    verify_games::<T>(cli::open_input(path)).finish("Games");
+}
+
+/// Decodes every game and replays it from `startpos`, checking moves, visit
+/// distributions, scores and results.
+pub fn verify_games<T: GameFormat>(reader: impl BufRead) -> Report {
+    let mut reader = Counted { inner: reader, bytes: 0 };
+    let mut report = Report::default();
+    let mut buffer = Vec::new();
+    let mut progress = Progress::counter("Verified", "Games", 1024 * 1024);
+
+    loop {
+        match reader.fill_buf() {
+            Ok([]) => break,
+            Ok(_) => {}
+            Err(err) => {
+                report.problem(report.bytes, "read error", || err.to_string());
+                break;
+            }
+        }
+
+        let offset = report.bytes;
+
+        if let Err(err) = T::deserialise_fast_into_buffer(&mut reader, &mut buffer) {
+            // whatever is left cannot be framed into records
+            let _ = reader.read_to_end(&mut Vec::new());
+            let trailing = reader.bytes - offset;
+            report.bytes = reader.bytes;
+            report.problem(offset, "truncated record", || format!("{err} ({trailing} trailing bytes)"));
+            break;
+        }
+
+        report.records += 1;
+        report.bytes = reader.bytes;
+
+        match T::decode(&buffer) {
+            Ok(game) => verify_game(&game, offset, &mut report),
+            Err(err) => report.problem(offset, "undecodable game", || err.to_string()),
+        }
+
+        progress.update(report.records);
+    }
+
+    progress.finish();
+    report
+}
+
+/// Counts the bytes taken from a reader, including those of a record that
+/// fails to read part way through.
+struct Counted<R> {
+    inner: R,
+    bytes: u64,
+}
+
+impl<R: BufRead> Read for Counted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.bytes += read as u64;
+        Ok(read)
+    }
+}
+
+impl<R: BufRead> BufRead for Counted<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.bytes += amt as u64;
+        self.inner.consume(amt);
+    }
+}
+
+fn verify_game<T: GameFormat>(game: &T, offset: u64, report: &mut Report) {
+    let mut pos = game.startpos();
+    let castling = game.castling();
+
+    if ![0.0, 0.5, 1.0].contains(&game.result()) {
+        report.problem(offset, "invalid result", || format!("result {}", game.result()));
+    }
+
+    if let Err(detail) = check_position(&pos) {
+        report.problem(offset, "invalid start position", || detail);
+        return;
+    }
+
+    let plies = game.plies();
+
+    if plies.is_empty() {
+        report.problem(offset, "empty game", || pos.as_fen());
+    }
+
+    for (ply, data) in plies.iter().enumerate() {
+        let mut legal = Vec::new();
+        pos.map_legal_moves(&castling, |mov| legal.push(mov));
+
+        let uci = |mov: Move| mov.to_uci(&castling);
+
+        if !legal.contains(&data.best_move) {
+            report.problem(offset, "illegal best move", || {
+                format!("{} at ply {ply} of {}", uci(data.best_move), pos.as_fen())
+            });
+            return;
+        }
+
+        if !T::score_in_range(data.score) {
+            report.problem(offset, "invalid score", || format!("score {} at ply {ply}", data.score));
+        }
+
+        if let Some(visits) = data.visits {
+            if let Some(&(mov, _)) = visits.iter().find(|(mov, _)| !legal.contains(mov)) {
+                report.problem(offset, "illegal visited move", || {
+                    format!("{} at ply {ply} of {}", uci(mov), pos.as_fen())
+                });
+            }
+
+            if !visits.iter().any(|&(mov, _)| mov == data.best_move) {
+                report.problem(offset, "best move not visited", || {
+                    format!("{} at ply {ply} of {}", uci(data.best_move), pos.as_fen())
+                });
+            }
+        }
+
+        pos.make(data.best_move, &castling);
+    }
+}
+
+/// Basic sanity of a position that move generation relies on.
+pub fn check_position(pos: &Position) -> Result<(), String> {
+    let bbs = pos.bbs();
+
+    if bbs[Side::WHITE] & bbs[Side::BLACK] > 0 {
+        return Err("squares occupied by both sides".to_string());
+    }
+
+    let mut pieces = 0;
+    for (i, &bb) in bbs[Piece::PAWN..=Piece::KING].iter().enumerate() {
+        if pieces & bb > 0 {
+            return Err(format!("overlapping piece boards at {}", Piece::PAWN + i));
+        }
+
+        pieces |= bb;
+    }
+
+    if pieces != bbs[Side::WHITE] | bbs[Side::BLACK] {
+        return Err("piece and colour boards disagree".to_string());
+    }
+
+    for side in [Side::WHITE, Side::BLACK] {
+        let kings = (bbs[side] & bbs[Piece::KING]).count_ones();
+        if kings != 1 {
+            return Err(format!("side {side} has {kings} kings"));
+        }
+    }
+
+    if bbs[Piece::PAWN] & 0xFF00_0000_0000_00FF > 0 {
+        return Err("pawns on the first or last rank".to_string());
+    }
+
+    if (bbs[pos.stm() ^ 1] & bbs[Piece::KING]) & pos.threats_by(pos.stm()) > 0 {
+        return Err("side not to move is in check".to_string());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use montyformat::{chess::Castling, MontyValueFormat};
+
+    use super::*;
+    use crate::testing::{self, STARTPOS};
+
+    /// Offset of the result byte in a `MontyValueFormat` record, after the
+    /// compressed position and rook files.
+    const RESULT_BYTE: usize = 32 + 6 + 4;
+
+    fn verify(bytes: &[u8]) -> Report {
+        verify_games::<MontyValueFormat>(bytes)
+    }
+
+    #[test]
+    fn clean_games_have_no_problems() {
+        let games = [
+            testing::value_game(STARTPOS, &["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Bxc6", "dxc6", "O-O"], 0.5),
+            testing::value_game("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", &["b8=Q+", "Kd7"], 1.0),
+        ];
+        let bytes = testing::binpack(&games);
+
+        let report = verify(&bytes);
+
+        assert_eq!(report.records, 2);
+        assert_eq!(report.bytes, bytes.len() as u64);
+        assert_eq!(report.total_problems(), 0);
+        assert!(report.first.is_none());
+    }
+
+    #[test]
+    fn counts_problems_by_kind() {
+        let clean = testing::value_game(STARTPOS, &["d4", "d5"], 0.0);
+
+        let mut illegal = testing::value_game(STARTPOS, &["e4", "e5"], 1.0);
+        // e2e5 is not a legal reply to 1... e5, or anything else
+        illegal.moves[1].best_move = Move::new(12, 36, 0);
+
+        let mut bad_result = testing::binpack(&[testing::value_game(STARTPOS, &["c4"], 0.5)]);
+        bad_result[RESULT_BYTE] = 3;
+
+        let mut bytes = testing::binpack(&[clean]);
+        let illegal_at = bytes.len() as u64;
+        bytes.extend(testing::binpack(&[illegal]));
+        bytes.extend(&bad_result);
+
+        let report = verify(&bytes);
+
+        assert_eq!(report.records, 3);
+        assert_eq!(report.total_problems(), 2);
+        assert_eq!(report.problems.get("illegal best move"), Some(&1));
+        assert_eq!(report.problems.get("invalid result"), Some(&1));
+
+        let (offset, detail) = report.first.unwrap();
+        assert_eq!(offset, illegal_at);
+        assert!(detail.starts_with("illegal best move: e2e5 at ply 1"), "{detail}");
+    }
+
+    #[test]
+    fn counts_truncated_records() {
+        let mut bytes = testing::binpack(&[testing::value_game(STARTPOS, &["e4"], 0.5)]);
+        let whole = bytes.len();
+        bytes.extend_from_slice(&bytes.clone()[..whole - 3]);
+
+        let report = verify(&bytes);
+
+        assert_eq!(report.records, 1);
+        assert_eq!(report.bytes, bytes.len() as u64);
+        assert_eq!(report.problems.get("truncated record"), Some(&1));
+        assert_eq!(report.first.unwrap().0, whole as u64);
+    }
+
+    #[test]
+    fn rejects_broken_positions() {
+        let check = |fen: &str| check_position(&Position::parse_fen(fen, &mut Castling::default()));
+
+        assert_eq!(check(STARTPOS), Ok(()));
+        assert_eq!(check("4k3/8/8/8/8/8/8/3KK3 w - - 0 1"), Err("side 0 has 2 kings".to_string()));
+        assert_eq!(check("4k3/8/8/8/8/8/8/P3K3 w - - 0 1"), Err("pawns on the first or last rank".to_string()));
+        assert_eq!(check("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1"), Err("side not to move is in check".to_string()));
+    }
+}
@@ -0,0 +1,99 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Where the optimiser writes the unquantised weights within a checkpoint,
+/// directly for the policy trainer and under `optimiser_state` for bullet's
+/// default trainer.
+pub const FILES: [&str; 2] = ["weights.bin", "optimiser_state/weights.bin"];
+
+/// The raw weights file of a checkpoint folder, if it has one. Pruned
+/// checkpoints only keep `quantised.bin`.
+pub fn find(dir: &Path) -> Option<PathBuf> {
+    FILES.iter().map(|file| dir.join(file)).find(|path| path.is_file())
+}
+
+/// Reads every named tensor, in the order they were written.
+pub fn read(path: &Path) -> io::Result<Vec<(String, Vec<f32>)>> {
+    parse(&std::fs::read(path)?).map_err(|err| io::Error::new(err.kind(), format!("{}: {err}", path.display())))
+}
+
+/// Each tensor is its id and values, both prefixed by their length as a
+/// little-endian `u64`.
+pub fn parse(mut bytes: &[u8]) -> io::Result<Vec<(String, Vec<f32>)>> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+    let mut tensors = Vec::new();
+
+    let split = |bytes: &mut &[u8], len: usize| -> io::Result<Vec<u8>> {
+        let (head, tail) = bytes.split_at_checked(len).ok_or_else(|| invalid("truncated weights"))?;
+        *bytes = tail;
+        Ok(head.to_vec())
+    };
+
+    let len = |bytes: Vec<u8>| usize::try_from(u64::from_le_bytes(bytes.try_into().unwrap())).unwrap_or(usize::MAX);
+
+    while !bytes.is_empty() {
+        let id_len = len(split(&mut bytes, 8)?);
+        let id = String::from_utf8(split(&mut bytes, id_len)?).map_err(|_| invalid("weight id is not UTF-8"))?;
+
+        let count = len(split(&mut bytes, 8)?);
+        let vals = split(&mut bytes, count.saturating_mul(4))?;
+
+        tensors.push((id, vals.chunks_exact(4).map(|x| f32::from_le_bytes(x.try_into().unwrap())).collect()));
+    }
+
+    Ok(tensors)
+}
+
+/// Removes the tensor called `id`, so that callers can take them in any order.
+pub fn take(tensors: &mut Vec<(String, Vec<f32>)>, id: &str) -> io::Result<Vec<f32>> {
+    let index = tensors
+        .iter()
+        .position(|(name, _)| name == id)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("no '{id}' weights")))?;
+
+    Ok(tensors.remove(index).1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(tensors: &[(&str, &[f32])]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        for (id, vals) in tensors {
+            bytes.extend_from_slice(&(id.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(id.as_bytes());
+            bytes.extend_from_slice(&(vals.len() as u64).to_le_bytes());
+            vals.iter().for_each(|x| bytes.extend_from_slice(&x.to_le_bytes()));
+        }
+
+        bytes
+    }
+
+    #[test]
+    fn parses_named_tensors() {
+        let bytes = encode(&[("l0w", &[0.5, -0.25, 0.99]), ("l0b", &[]), ("l1w", &[1.0])]);
+        let mut tensors = parse(&bytes).unwrap();
+
+        assert_eq!(tensors.iter().map(|(id, _)| id.as_str()).collect::<Vec<_>>(), ["l0w", "l0b", "l1w"]);
+        assert_eq!(take(&mut tensors, "l1w").unwrap(), [1.0]);
+        assert_eq!(take(&mut tensors, "l0w").unwrap(), [0.5, -0.25, 0.99]);
+        assert!(take(&mut tensors, "l1w").is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        let bytes = encode(&[("l0w", &[0.5, -0.25])]);
+
+        for len in [1, 8, 10, 11, 12, bytes.len() - 1] {
+            assert_eq!(parse(&bytes[..len]).unwrap_err().kind(), io::ErrorKind::InvalidData, "{len}");
+        }
+
+        let mut huge = encode(&[("l0w", &[])]);
+        huge[11..19].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(parse(&huge).is_err());
+    }
+}
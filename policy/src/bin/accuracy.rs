@@ -0,0 +1,144 @@
+use std::io::BufRead;
+
+use montyformat::chess::Move;
+use montytrain_common::{
+    cli::{self, Cli},
+    epd::Epd,
+    fen, san,
+};
+use policy::{config, infer::QuantisedPolicy, inputs::PolicyInputs};
+
+#[derive(Default)]
+struct Totals {
+    best: u64,
+    top1: u64,
+    topk: u64,
+    rank_sum: u64,
+    avoid: u64,
+    avoided: u64,
+    skipped: u64,
+}
+
+/// Runs a policy net over an EPD suite and scores it against the `bm`
+/// (best move) and `am` (avoid move) operations of each position.
+fn main() {
+    let args = Cli::new("accuracy", "Measures policy top-k accuracy on an EPD best-move test suite.")
+        .required("net", "quantised.bin, or checkpoint folder containing it")
+        .required("suite", "EPD file with 'bm' and/or 'am' operations")
+        .default("top-k", "3", "k for the top-k hit rate")
+        .flag("quiet", "Only print the summary, not each failure")
+        .parse();
+
+    let net_path = args.get("net");
+    let feature_set = config::inputs();
+    let k: usize = args.parse("top-k");
+    let quiet = args.flag("quiet");
+
+    let net = QuantisedPolicy::from_file(net_path, feature_set.num_inputs())
+        .unwrap_or_else(|err| cli::fail(format!("could not load '{net_path}': {err}")));
+
+    println!("Net: {net_path} (hl {})", net.hidden_size());
+
+    let mut totals = Totals::default();
+
+    for (index, line) in cli::open_input(args.get("suite")).lines().enumerate() {
+        let line = line.unwrap_or_else(|err| cli::fail(format!("could not read suite: {err}")));
+
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let Some(epd) = Epd::parse(&line) else {
+            if !quiet {
+                println!("SKIP line {}: fewer than four fields", index + 1);
+            }
+
+            totals.skipped += 1;
+            continue;
+        };
+
+        let id = epd.get("id").map_or_else(|| format!("line {}", index + 1), str::to_string);
+
+        let (pos, castling) = match fen::parse(&epd.fen) {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                if !quiet {
+                    println!("SKIP {id} (line {}): invalid position: {err}", index + 1);
+                }
+
+                totals.skipped += 1;
+                continue;
+            }
+        };
+
+        let moves_of = |code: &str| -> Option<Vec<Move>> {
+            let operand = epd.get(code)?;
+            operand.split_whitespace().map(|text| san::parse_move(&pos, &castling, text)).collect()
+        };
+
+        let (best, avoid) = (moves_of("bm"), moves_of("am"));
+
+        if best.is_none() && avoid.is_none() {
+            if !quiet {
+                println!("SKIP {id}: no legal 'bm' or 'am' moves");
+            }
+
+            totals.skipped += 1;
+            continue;
+        }
+
+        let mut policy = net.policy(&feature_set, &pos, &castling);
+
+        if policy.is_empty() {
+            totals.skipped += 1;
+            continue;
+        }
+
+        policy.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let (top, top_prob) = policy[0];
+        let describe = |mov: Move| san::to_san(&pos, &castling, mov);
+
+        if let Some(best) = best {
+            let rank = policy.iter().position(|(mov, _)| best.contains(mov)).unwrap_or(policy.len()) + 1;
+
+            totals.best += 1;
+            totals.rank_sum += rank as u64;
+            totals.top1 += u64::from(rank == 1);
+            totals.topk += u64::from(rank <= k);
+
+            if rank > 1 && !quiet {
+                let wanted = best.iter().map(|&mov| describe(mov)).collect::<Vec<_>>().join(" ");
+                let prob = policy[rank - 1].1;
+                println!(
+                    "FAIL {id}: bm {wanted} at rank {rank} ({:.1}%), top {} ({:.1}%)",
+                    prob * 100.0,
+                    describe(top),
+                    top_prob * 100.0
+                );
+            }
+        }
+
+        if let Some(avoid) = avoid {
+            totals.avoid += 1;
+
+            if avoid.contains(&top) {
+                if !quiet {
+                    println!("FAIL {id}: am {} is the top move ({:.1}%)", describe(top), top_prob * 100.0);
+                }
+            } else {
+                totals.avoided += 1;
+            }
+        }
+    }
+
+    let pct = |n: u64, total: u64| n as f64 / total.max(1) as f64 * 100.0;
+
+    println!("Best Move Positions : {}", totals.best);
+    println!(" - Top 1            : {} ({:.2}%)", totals.top1, pct(totals.top1, totals.best));
+    println!(" - Top {k:<2}           : {} ({:.2}%)", totals.topk, pct(totals.topk, totals.best));
+    println!(" - Mean Rank        : {:.2}", totals.rank_sum as f64 / totals.best.max(1) as f64);
+    println!("Avoid Move Positions: {}", totals.avoid);
+    println!(" - Avoided          : {} ({:.2}%)", totals.avoided, pct(totals.avoided, totals.avoid));
+    println!("Skipped             : {}", totals.skipped);
+}
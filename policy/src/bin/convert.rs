@@ -1,24 +1,28 @@
-use std::{
-    fs::File,
-    io::{BufReader, BufWriter},
-};
+use std::io::Write;
 
 use montyformat::{MontyFormat, MontyValueFormat};
+use montytrain_common::{
+    cli::{self, Cli},
+    progress::Progress,
+};
 
 fn main() {
-    let mut args = std::env::args();
-    args.next();
+    let args = Cli::new("convert", "Converts MontyFormat policy data into MontyValueFormat value data.")
+        .required("input", "MontyFormat binpack to read")
+        .required("output", "MontyValueFormat binpack to write")
+        .parse();
 
-    let inp_path = args.next().unwrap();
-    let out_path = args.next().unwrap();
+    let inp_path = args.get("input");
+    let out_path = args.get("output");
 
-    let mut reader = BufReader::new(File::open(inp_path).unwrap());
-    let mut writer = BufWriter::new(File::create(out_path).unwrap());
+    let mut reader = cli::open_input(inp_path);
+    let mut writer = cli::create_output(out_path, &[inp_path]);
 
     let mut positions = 0;
-    let mut games = 0;
+    let mut games = 0u64;
 
     let mut moves = Vec::new();
+    let mut progress = Progress::counter("Converted", "games", 16384);
 
     while let Ok(game) = MontyFormat::deserialise_from(&mut reader) {
         moves.clear();
@@ -34,16 +38,17 @@ fn main() {
             stm = 1 - stm;
         }
 
-        MontyValueFormat::serialise_into(&value, &mut writer).unwrap();
+        MontyValueFormat::serialise_into(&value, &mut writer)
+            .unwrap_or_else(|err| cli::fail(format!("could not write to '{out_path}': {err}")));
 
         games += 1;
         moves = value.moves;
 
-        if games % 16384 == 0 {
-            println!("Converted {games} games.");
-        }
+        progress.update(games);
     }
 
+    writer.flush().unwrap_or_else(|err| cli::fail(format!("could not write to '{out_path}': {err}")));
+
     println!("Positions    : {positions}");
     println!("Games        : {games}");
     println!("Avg Game Len : {:.2}", positions as f64 / games as f64);
@@ -0,0 +1,51 @@
+use montyformat::MontyFormat;
+use montytrain_common::{
+    cli::{self, Cli},
+    neurons::NeuronStats,
+    pgn::STARTPOS,
+    sample,
+};
+use policy::{config, infer::QuantisedPolicy, inputs::PolicyInputs};
+
+fn main() {
+    let cli = Cli::new("dead-neurons", "Reports dead and saturated l0 neurons of a policy net over a position sample.")
+        .required("net", "quantised.bin, or checkpoint folder containing it")
+        .optional("output", "Write a pruned quantised.bin without the pairs that were never active");
+
+    let args = sample::options(cli).parse();
+
+    if !sample::has_source(&args) {
+        cli::fail("no positions given, use --binpack, --fens or --fen");
+    }
+
+    let net_path = args.get("net");
+    let feature_set = config::inputs();
+
+    let net = QuantisedPolicy::from_file(net_path, feature_set.num_inputs())
+        .unwrap_or_else(|err| cli::fail(format!("could not load '{net_path}': {err}")));
+
+    println!("Net: {net_path} (hl {})", net.hidden_size());
+
+    let mut stats = NeuronStats::new(net.hidden_size());
+
+    for sample in sample::load::<MontyFormat>(&args, STARTPOS) {
+        stats.record(&net.l0(&feature_set, &sample.pos), 1.0);
+    }
+
+    stats.print();
+
+    if let Some(out_path) = args.get_opt("output") {
+        let live = stats.live_pairs();
+
+        if live.is_empty() {
+            cli::fail("no pair was ever active, not writing an empty net");
+        }
+
+        let pruned = net.pruned(&live);
+
+        std::fs::write(out_path, pruned.to_bytes())
+            .unwrap_or_else(|err| cli::fail(format!("could not write '{out_path}': {err}")));
+
+        println!("Written {out_path} (hl {})", pruned.hidden_size());
+    }
+}
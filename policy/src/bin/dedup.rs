@@ -0,0 +1,6 @@
+use montyformat::MontyFormat;
+use montytrain_common::dedup;
+
+fn main() {
+    dedup::main::<MontyFormat>("dedup", "Reports and removes duplicated games and positions in MontyFormat binpacks.");
+}
@@ -0,0 +1,6 @@
+use montyformat::MontyFormat;
+use montytrain_common::export;
+
+fn main() {
+    export::main::<MontyFormat>("export", "Exports MontyFormat positions with visit distributions as text lines.");
+}
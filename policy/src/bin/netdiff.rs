@@ -0,0 +1,91 @@
+use std::path::Path;
+
+use montyformat::{chess::Move, MontyFormat};
+use montytrain_common::{
+    cli::{self, Cli},
+    netdiff,
+    pgn::STARTPOS,
+    sample, weights,
+};
+use policy::{config, infer::QuantisedPolicy, inputs::PolicyInputs};
+
+/// Compares the weights of two policy nets, and the distributions they give
+/// over a sample of positions.
+fn main() {
+    let cli = Cli::new("netdiff", "Compares two policy checkpoints or quantised nets of the same architecture.")
+        .required("a", "First quantised.bin, or checkpoint folder containing it")
+        .required("b", "Second quantised.bin, or checkpoint folder containing it");
+
+    let args = sample::options(cli).parse();
+    let feature_set = config::inputs();
+
+    // saturation only means something before quantisation, so compare the
+    // raw weights whenever both checkpoints still have them
+    let raw = ["a", "b"].iter().all(|name| weights::find(Path::new(args.get(name))).is_some());
+
+    let load = |path: &str| {
+        let net = if raw {
+            QuantisedPolicy::from_checkpoint(Path::new(path), feature_set.num_inputs())
+        } else {
+            QuantisedPolicy::from_file(path, feature_set.num_inputs())
+        };
+
+        net.unwrap_or_else(|err| cli::fail(format!("could not load '{path}': {err}")))
+    };
+
+    let (a, b) = (load(args.get("a")), load(args.get("b")));
+
+    if a.hidden_size() != b.hidden_size() {
+        cli::fail(format!("hidden sizes differ: {} vs {}", a.hidden_size(), b.hidden_size()));
+    }
+
+    println!("A: {} (hl {})", args.get("a"), a.hidden_size());
+    println!("B: {} (hl {})", args.get("b"), b.hidden_size());
+    println!("Weights: {}", if raw { "raw" } else { "quantised" });
+    println!();
+
+    netdiff::print_header();
+    for ((name, x), (_, y)) in a.tensors().into_iter().zip(b.tensors()) {
+        netdiff::print_tensor(name, x, y);
+    }
+
+    let samples = sample::load::<MontyFormat>(&args, STARTPOS);
+
+    let mut positions = 0;
+    let mut kl_sum = 0.0;
+    let mut kl_max = 0.0f64;
+    let mut agree = 0;
+
+    let top = |policy: &[(Move, f32)]| policy.iter().max_by(|x, y| x.1.total_cmp(&y.1)).map(|&(mov, _)| mov);
+
+    for sample in &samples {
+        let pa = a.policy(&feature_set, &sample.pos, &sample.castling);
+        let pb = b.policy(&feature_set, &sample.pos, &sample.castling);
+
+        if pa.is_empty() {
+            continue;
+        }
+
+        let kl = kl_divergence(&pa, &pb);
+
+        positions += 1;
+        kl_sum += kl;
+        kl_max = kl_max.max(kl);
+        agree += usize::from(top(&pa) == top(&pb));
+    }
+
+    println!();
+    println!("Positions     : {positions}");
+    println!("Mean KL(A||B) : {:.5}", kl_sum / positions.max(1) as f64);
+    println!("Max KL(A||B)  : {kl_max:.5}");
+    println!("Top-1 Agree   : {:.2}%", 100.0 * agree as f64 / positions.max(1) as f64);
+}
+
+/// KL(P || Q) of two policies over the same moves in the same order.
+fn kl_divergence(p: &[(Move, f32)], q: &[(Move, f32)]) -> f64 {
+    p.iter()
+        .zip(q)
+        .filter(|(&(_, p), _)| p > 0.0)
+        .map(|(&(_, p), &(_, q))| f64::from(p) * (f64::from(p) / f64::from(q.max(1e-12))).ln())
+        .sum()
+}
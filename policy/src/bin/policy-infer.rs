@@ -0,0 +1,56 @@
+use montyformat::MontyFormat;
+use montytrain_common::{
+    cli::{self, Cli},
+    pgn::STARTPOS,
+    sample::{self, json_string},
+    san,
+};
+use policy::{config, infer::QuantisedPolicy, inputs::PolicyInputs};
+
+fn main() {
+    let cli = Cli::new("policy-infer", "Evaluates positions on the CPU with a quantised policy network.")
+        .required("net", "quantised.bin, or checkpoint folder containing it")
+        .default("top", "5", "Moves to print for each position, without --json")
+        .flag("json", "Write one JSON line per position with the full distribution");
+
+    let args = sample::options(cli).parse();
+
+    let net_path = args.get("net");
+    let feature_set = config::inputs();
+    let top: usize = args.parse("top");
+    let json = args.flag("json");
+
+    let net = QuantisedPolicy::from_file(net_path, feature_set.num_inputs())
+        .unwrap_or_else(|err| cli::fail(format!("could not load '{net_path}': {err}")));
+
+    if !json {
+        println!("Loaded {net_path} with hl = {}", net.hidden_size());
+    }
+
+    for sample in sample::load::<MontyFormat>(&args, STARTPOS) {
+        let mut policy = net.policy(&feature_set, &sample.pos, &sample.castling);
+        policy.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        if json {
+            let moves = policy
+                .iter()
+                .map(|&(mov, p)| format!("{{\"move\":{},\"p\":{p:.6}}}", json_string(&mov.to_uci(&sample.castling))))
+                .collect::<Vec<_>>();
+
+            let mut line = format!("{{\"fen\":{},\"policy\":[{}]", json_string(&sample.fen), moves.join(","));
+
+            if let Some(stored) = sample.stored_json::<MontyFormat>() {
+                line.push_str(&format!(",\"stored\":{stored}"));
+            }
+
+            line.push('}');
+            println!("{line}");
+        } else {
+            println!("FEN   : {}", sample.fen);
+
+            for &(mov, p) in policy.iter().take(top) {
+                println!("  {:<8} {:>6.2}%", san::to_san(&sample.pos, &sample.castling, mov), p * 100.0);
+            }
+        }
+    }
+}
@@ -0,0 +1,9 @@
+use montyformat::MontyFormat;
+use montytrain_common::pgn;
+
+fn main() {
+    pgn::main::<MontyFormat>(
+        "to-pgn",
+        "Writes MontyFormat games as PGN, with scores and top visited moves as comments.",
+    );
+}
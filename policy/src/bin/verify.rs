@@ -0,0 +1,6 @@
+use montyformat::MontyFormat;
+use montytrain_common::verify;
+
+fn main() {
+    verify::main::<MontyFormat>("verify", "Fully decodes and replays every game of a MontyFormat binpack.");
+}
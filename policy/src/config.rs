@@ -0,0 +1,28 @@
+use montytrain_common::cli;
+
+use crate::{
+    inputs::ThreatDefenceInputs,
+    lr::{LrParams, LrSchedule},
+};
+
+/// The network being trained, shared by the trainer and the tools that
+/// consume its exports.
+pub const HIDDEN_SIZE: usize = 16384;
+
+pub type Inputs = ThreatDefenceInputs;
+
+pub fn inputs() -> Inputs {
+    ThreatDefenceInputs
+}
+
+/// Default of `--lr-schedule`, one of [`LrSchedule::NAMES`].
+pub const LR_SCHEDULE: &str = "exponential";
+
+pub const LR_PARAMS: LrParams = LrParams { initial_lr: 0.001, final_lr: 0.00001, warmup: 20, step: 200, gamma: 0.3 };
+
+/// The schedule called `name`, exiting with the valid names if there is none.
+pub fn lr_schedule(name: &str, end_superbatch: usize) -> LrSchedule {
+    LrSchedule::from_name(name, &LR_PARAMS, end_superbatch).unwrap_or_else(|| {
+        cli::fail(format!("unknown LR schedule '{name}', expected one of: {}", LrSchedule::NAMES.join(", ")))
+    })
+}
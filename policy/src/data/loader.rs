@@ -8,62 +8,66 @@ use bullet_core::{
 use montyformat::chess::Move;
 
 use super::reader::{DataReader, DecompressedData};
-use crate::inputs::{self, INPUT_SIZE, MAX_ACTIVE_BASE, MAX_MOVES, NUM_MOVES_INDICES};
+use crate::inputs::{self, PolicyInputs, MAX_MOVES, NUM_MOVES_INDICES};
 
 #[derive(Clone)]
-pub struct MontyDataLoader {
+pub struct MontyDataLoader<I: PolicyInputs> {
     reader: DataReader,
     threads: usize,
+    inputs: I,
 }
 
-impl MontyDataLoader {
-    pub fn new(path: &str, buffer_size_mb: usize, threads: usize) -> Self {
-        Self { reader: DataReader::new(path, buffer_size_mb), threads }
+impl<I: PolicyInputs> MontyDataLoader<I> {
+    pub fn new(path: &str, buffer_size_mb: usize, threads: usize, inputs: I) -> Self {
+        Self { reader: DataReader::new(path, buffer_size_mb), threads, inputs }
     }
 }
 
-impl DataLoader for MontyDataLoader {
+impl<I: PolicyInputs> DataLoader for MontyDataLoader<I> {
     type Error = DataLoadingError;
 
     fn map_batches<F: FnMut(PreparedBatchHost) -> bool>(self, batch_size: usize, mut f: F) -> Result<(), Self::Error> {
-        self.reader.map_batches(batch_size, |batch| f(prepare(batch, self.threads)));
+        self.reader.map_batches(batch_size, |batch| f(prepare(&self.inputs, batch, self.threads)));
 
         Ok(())
     }
 }
 
-pub fn prepare(data: &[DecompressedData], threads: usize) -> PreparedBatchHost {
+pub fn prepare<I: PolicyInputs>(feature_set: &I, data: &[DecompressedData], threads: usize) -> PreparedBatchHost {
     let batch_size = data.len();
     let chunk_size = batch_size.div_ceil(threads);
 
-    let mut inputs = vec![0; MAX_ACTIVE_BASE * batch_size];
+    let num_inputs = feature_set.num_inputs();
+    let max_active = feature_set.max_active();
+
+    let mut inputs = vec![0; max_active * batch_size];
     let mut moves = vec![0; MAX_MOVES * batch_size];
     let mut dist = vec![0.0; MAX_MOVES * batch_size];
 
     std::thread::scope(|s| {
         for (((data_chunk, input_chunk), moves_chunk), dist_chunk) in data
             .chunks(chunk_size)
-            .zip(inputs.chunks_mut(MAX_ACTIVE_BASE * chunk_size))
+            .zip(inputs.chunks_mut(max_active * chunk_size))
             .zip(moves.chunks_mut(MAX_MOVES * chunk_size))
             .zip(dist.chunks_mut(MAX_MOVES * chunk_size))
         {
             s.spawn(move || {
                 for (i, point) in data_chunk.iter().enumerate() {
-                    let input_offset = MAX_ACTIVE_BASE * i;
+                    let input_offset = max_active * i;
                     let moves_offset = MAX_MOVES * i;
 
                     let mut j = 0;
-                    inputs::map_base_inputs(&point.pos, |feat| {
-                        assert!(feat < INPUT_SIZE);
+                    feature_set.map_features(&point.pos, |feat| {
+                        assert!(feat < num_inputs);
                         input_chunk[input_offset + j] = feat as i32;
                         j += 1;
                     });
 
-                    for k in j..MAX_ACTIVE_BASE {
+                    for k in j..max_active {
                         input_chunk[input_offset + k] = -1;
                     }
 
-                    assert!(j <= MAX_ACTIVE_BASE, "More inputs provided than the specified maximum!");
+                    assert!(j <= max_active, "More inputs provided than the specified maximum!");
 
                     let mut total = 0;
                     let mut distinct = 0;
@@ -98,7 +102,7 @@ pub fn prepare(data: &[DecompressedData], threads: usize) -> PreparedBatchHost {
     unsafe {
         prep.inputs.insert(
             "inputs".to_string(),
-            HostMatrix::Sparse(HostSparseMatrix::new(inputs, batch_size, Shape::new(INPUT_SIZE, 1), MAX_ACTIVE_BASE)),
+            HostMatrix::Sparse(HostSparseMatrix::new(inputs, batch_size, Shape::new(num_inputs, 1), max_active)),
         );
 
         prep.inputs.insert(
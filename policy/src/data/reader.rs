@@ -1,14 +1,10 @@
-use std::{
-    fs::File,
-    io::BufReader,
-    sync::mpsc,
-    time::{SystemTime, UNIX_EPOCH},
-};
+use std::{fs::File, io::BufReader, sync::mpsc};
 
 use montyformat::{
     chess::{Castling, Position},
     MontyFormat,
 };
+use montytrain_common::rng::Rand;
 
 use crate::inputs::MAX_MOVES;
 
@@ -98,10 +94,10 @@ impl DataReader {
 }
 
 fn shuffle(data: &mut [DecompressedData]) {
-    let mut rng = Rand::with_seed();
+    let mut rng = Rand::from_clock();
 
     for i in (0..data.len()).rev() {
-        let idx = rng.rng() as usize % (i + 1);
+        let idx = rng.rand() as usize % (i + 1);
         data.swap(idx, i);
     }
 }
@@ -128,21 +124,3 @@ fn parse_into_buffer(game: MontyFormat, buffer: &mut Vec<DecompressedData>) {
         pos.make(data.best_move, &castling);
     }
 }
-
-pub struct Rand(u64);
-
-impl Rand {
-    pub fn with_seed() -> Self {
-        let seed = SystemTime::now().duration_since(UNIX_EPOCH).expect("Guaranteed increasing.").as_micros() as u64
-            & 0xFFFF_FFFF;
-
-        Self(seed)
-    }
-
-    pub fn rng(&mut self) -> u64 {
-        self.0 ^= self.0 << 13;
-        self.0 ^= self.0 >> 7;
-        self.0 ^= self.0 << 17;
-        self.0
-    }
-}
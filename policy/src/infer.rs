@@ -0,0 +1,307 @@
+use std::path::Path;
+
+use montyformat::chess::{Castling, Move, Position};
+use montytrain_common::{sample::Sample, weights};
+
+use crate::inputs::{self, PolicyInputs, NUM_MOVES_INDICES};
+
+/// Scale that `model::save_quantised` multiplies every weight by.
+pub const QA: f32 = 128.0;
+
+/// A network loaded from `quantised.bin` and dequantised, or from the raw
+/// weights of a checkpoint, for CPU inference. `l0w` is stored per input and
+/// `l1w` per move index, as in the trainer.
+pub struct QuantisedPolicy {
+    hl: usize,
+    num_inputs: usize,
+    l0w: Vec<f32>,
+    l0b: Vec<f32>,
+    l1w: Vec<f32>,
+    l1b: Vec<f32>,
+}
+
+impl QuantisedPolicy {
+    /// Accepts the file itself or the checkpoint folder containing it. A
+    /// folder without `quantised.bin` is loaded with [`Self::from_checkpoint`].
+    pub fn from_file(path: &str, num_inputs: usize) -> std::io::Result<Self> {
+        let path = Path::new(path);
+
+        if path.is_dir() && !path.join("quantised.bin").is_file() {
+            return Self::from_checkpoint(path, num_inputs);
+        }
+
+        let path = if path.is_dir() { path.join("quantised.bin") } else { path.to_path_buf() };
+
+        let bytes = std::fs::read(&path)?;
+        Self::from_bytes(&bytes, num_inputs).ok_or_else(|| {
+            let msg = format!("{} does not match a policy net with {num_inputs} inputs", path.display());
+            std::io::Error::new(std::io::ErrorKind::InvalidData, msg)
+        })
+    }
+
+    /// Loads the unquantised weights the optimiser saved in a checkpoint
+    /// folder, see [`weights::FILES`].
+    pub fn from_checkpoint(dir: &Path, num_inputs: usize) -> std::io::Result<Self> {
+        let path = weights::find(dir).ok_or_else(|| {
+            let msg = format!("{} has neither quantised.bin nor raw weights", dir.display());
+            std::io::Error::new(std::io::ErrorKind::NotFound, msg)
+        })?;
+
+        let mut tensors = weights::read(&path)?;
+        let mut take = |id| weights::take(&mut tensors, id);
+        let (l0w, l0b, l1w, l1b) = (take("l0w")?, take("l0b")?, take("l1w")?, take("l1b")?);
+
+        let hl = l0b.len();
+        let sizes = [num_inputs * hl, hl / 2 * NUM_MOVES_INDICES, NUM_MOVES_INDICES];
+
+        if hl == 0 || !hl.is_multiple_of(2) || [l0w.len(), l1w.len(), l1b.len()] != sizes {
+            let msg = format!("{} does not match a policy net with {num_inputs} inputs", path.display());
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, msg));
+        }
+
+        Ok(Self { hl, num_inputs, l0w, l0b, l1w, l1b })
+    }
+
+    /// The hidden size is inferred from the length, every weight is one byte.
+    pub fn from_bytes(bytes: &[u8], num_inputs: usize) -> Option<Self> {
+        let per_neuron = num_inputs + 1 + NUM_MOVES_INDICES / 2;
+        let hl = bytes.len().checked_sub(NUM_MOVES_INDICES)? / per_neuron;
+
+        if hl == 0 || !hl.is_multiple_of(2) || hl * per_neuron + NUM_MOVES_INDICES != bytes.len() {
+            return None;
+        }
+
+        let mut vals = bytes.iter().map(|&b| f32::from(b as i8) / QA);
+        let mut take = |n: usize| vals.by_ref().take(n).collect::<Vec<_>>();
+
+        let l0w = take(num_inputs * hl);
+        let l0b = take(hl);
+        let l1w = take(hl / 2 * NUM_MOVES_INDICES);
+        let l1b = take(NUM_MOVES_INDICES);
+
+        Some(Self { hl, num_inputs, l0w, l0b, l1w, l1b })
+    }
+
+    pub fn hidden_size(&self) -> usize {
+        self.hl
+    }
+
+    pub fn num_inputs(&self) -> usize {
+        self.num_inputs
+    }
+
+    /// Named tensors in the order they are saved.
+    pub fn tensors(&self) -> [(&'static str, &[f32]); 4] {
+        [("l0w", &self.l0w), ("l0b", &self.l0b), ("l1w", &self.l1w), ("l1b", &self.l1b)]
+    }
+
+    /// `l0` after CReLU, before the pairwise multiplication.
+    pub fn l0<I: PolicyInputs>(&self, feature_set: &I, pos: &Position) -> Vec<f32> {
+        let mut acc = self.l0b.clone();
+
+        feature_set.map_features(pos, |feat| {
+            let col = &self.l0w[feat * self.hl..(feat + 1) * self.hl];
+            for (a, &w) in acc.iter_mut().zip(col) {
+                *a += w;
+            }
+        });
+
+        acc.iter().map(|a| a.clamp(0.0, 1.0)).collect()
+    }
+
+    /// `l0` after CReLU and the pairwise multiplication.
+    pub fn hidden<I: PolicyInputs>(&self, feature_set: &I, pos: &Position) -> Vec<f32> {
+        let l0 = self.l0(feature_set, pos);
+        let half = self.hl / 2;
+        (0..half).map(|i| l0[i] * l0[i + half]).collect()
+    }
+
+    /// Mean cross-entropy against the stored visit distributions, and how
+    /// often the top move is the most visited one. Samples without visits
+    /// are skipped.
+    pub fn validate<I: PolicyInputs>(&self, feature_set: &I, samples: &[Sample]) -> (f32, f32) {
+        let mut loss = 0.0;
+        let mut correct = 0;
+        let mut count = 0;
+
+        for sample in samples {
+            let Some(visits) = sample.stored.as_ref().and_then(|stored| stored.visits.as_ref()) else {
+                continue;
+            };
+
+            let policy = self.policy(feature_set, &sample.pos, &sample.castling);
+            let total = visits.iter().map(|&(_, n)| n as f32).sum::<f32>().max(1.0);
+            let prob = |mov: Move| policy.iter().find(|&&(m, _)| m == mov).map_or(0.0, |&(_, p)| p);
+
+            loss -= visits.iter().map(|&(mov, n)| n as f32 / total * prob(mov).max(1e-9).ln()).sum::<f32>();
+
+            let top = policy.iter().max_by(|a, b| a.1.total_cmp(&b.1)).map(|&(mov, _)| mov);
+            let most_visited = visits.iter().max_by_key(|&&(_, n)| n).map(|&(mov, _)| mov);
+            correct += usize::from(top.is_some() && top == most_visited);
+            count += 1;
+        }
+
+        let n = count.max(1) as f32;
+        (loss / n, correct as f32 / n)
+    }
+
+    /// Keeps only the given pairs `(j, j + hl / 2)` of `l0` neurons, in order.
+    pub fn pruned(&self, pairs: &[usize]) -> Self {
+        let half = self.hl / 2;
+        let neurons = pairs.iter().copied().chain(pairs.iter().map(|&j| j + half)).collect::<Vec<_>>();
+
+        let l0w =
+            (0..self.num_inputs).flat_map(|feat| neurons.iter().map(move |&i| self.l0w[feat * self.hl + i])).collect();
+        let l0b = neurons.iter().map(|&i| self.l0b[i]).collect();
+        let l1w = (0..NUM_MOVES_INDICES).flat_map(|idx| pairs.iter().map(move |&j| self.l1w[idx * half + j])).collect();
+
+        Self { hl: neurons.len(), num_inputs: self.num_inputs, l0w, l0b, l1w, l1b: self.l1b.clone() }
+    }
+
+    /// Quantises back to the layout of `model::save_quantised`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.tensors().iter().flat_map(|(_, vals)| vals.iter().map(|&x| (x * QA).round() as i8 as u8)).collect()
+    }
+
+    /// Softmaxed policy over the legal moves, in move generation order.
+    pub fn policy<I: PolicyInputs>(&self, feature_set: &I, pos: &Position, castling: &Castling) -> Vec<(Move, f32)> {
+        let hidden = self.hidden(feature_set, pos);
+        let half = self.hl / 2;
+
+        let mut logits = Vec::new();
+        pos.map_legal_moves(castling, |mov| {
+            let idx = inputs::map_move_to_index(pos, mov);
+            let weights = &self.l1w[idx * half..(idx + 1) * half];
+            let logit = self.l1b[idx] + weights.iter().zip(&hidden).map(|(w, h)| w * h).sum::<f32>();
+            logits.push((mov, logit));
+        });
+
+        let max = logits.iter().fold(f32::NEG_INFINITY, |a, &(_, l)| a.max(l));
+        let mut total = 0.0;
+
+        for (_, l) in &mut logits {
+            *l = (*l - max).exp();
+            total += *l;
+        }
+
+        for (_, l) in &mut logits {
+            *l /= total;
+        }
+
+        logits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inputs::ThreatDefenceInputs;
+
+    use super::*;
+
+    const HL: usize = 4;
+    const FEN: &str = "8/8/8/8/8/8/8/K6k w - - 0 1";
+
+    fn position() -> (Position, Castling) {
+        let mut castling = Castling::default();
+        let pos = Position::parse_fen(FEN, &mut castling);
+        (pos, castling)
+    }
+
+    /// Index of each legal move, in move generation order.
+    fn move_indices(pos: &Position, castling: &Castling) -> Vec<(String, usize)> {
+        let mut indices = Vec::new();
+        pos.map_legal_moves(castling, |mov| indices.push((mov.to_uci(castling), inputs::map_move_to_index(pos, mov))));
+        indices
+    }
+
+    /// A net with four neurons, written weight by weight in the layout of
+    /// `quantised.bin`: `l0w` per feature, `l0b`, `l1w` per move index, `l1b`.
+    /// Every weight is a multiple of `1 / QA`, so none are rounded.
+    fn reference_net() -> Vec<u8> {
+        let inputs = ThreatDefenceInputs;
+        let (pos, castling) = position();
+        let num_inputs = inputs.num_inputs();
+
+        let l0b_at = num_inputs * HL;
+        let l1w_at = l0b_at + HL;
+        let l1b_at = l1w_at + HL / 2 * NUM_MOVES_INDICES;
+
+        let mut bytes = vec![0i8; l1b_at + NUM_MOVES_INDICES];
+
+        let mut feats = Vec::new();
+        inputs.map_features(&pos, |feat| feats.push(feat));
+        assert_eq!(feats.len(), 2);
+
+        for (feat, col) in feats.iter().zip([[32, 32, 0, 64], [32, -16, 32, 127]]) {
+            bytes[feat * HL..(feat + 1) * HL].copy_from_slice(&col);
+        }
+
+        bytes[l0b_at..l1w_at].copy_from_slice(&[0, 16, 64, 64]);
+
+        for (uci, idx) in move_indices(&pos, &castling) {
+            let (weights, bias) = match uci.as_str() {
+                "a1a2" => ([64, 0], 0),
+                "a1b1" => ([0, -64], 32),
+                "a1b2" => ([0, 0], -128),
+                _ => unreachable!("{uci}"),
+            };
+
+            bytes[l1w_at + idx * HL / 2..l1w_at + (idx + 1) * HL / 2].copy_from_slice(&weights);
+            bytes[l1b_at + idx] = bias;
+        }
+
+        bytes.iter().map(|&b| b as u8).collect()
+    }
+
+    #[test]
+    fn matches_a_hand_computed_policy() {
+        let inputs = ThreatDefenceInputs;
+        let net = QuantisedPolicy::from_bytes(&reference_net(), inputs.num_inputs()).unwrap();
+        let (pos, castling) = position();
+
+        assert_eq!(net.hidden_size(), HL);
+
+        // l0 = clamp([0, 0.125, 0.5, 0.5] + [0.25, 0.25, 0, 0.5] + [0.25, -0.125, 0.25, 0.99])
+        assert_eq!(net.l0(&inputs, &pos), [0.5, 0.25, 0.75, 1.0]);
+        assert_eq!(net.hidden(&inputs, &pos), [0.375, 0.25]);
+
+        let logits = [("a1a2", 0.5 * 0.375), ("a1b1", 0.25 - 0.5 * 0.25), ("a1b2", -1.0)];
+        let total = logits.iter().map(|(_, logit)| f32::exp(*logit)).sum::<f32>();
+
+        let policy = net.policy(&inputs, &pos, &castling);
+        assert_eq!(policy.len(), logits.len());
+
+        for (mov, prob) in policy {
+            let uci = mov.to_uci(&castling);
+            let (_, logit) = logits.iter().find(|(name, _)| *name == uci).unwrap();
+            assert!((prob - logit.exp() / total).abs() < 1e-6, "{uci}: {prob}");
+        }
+    }
+
+    #[test]
+    fn pruning_keeps_the_chosen_pairs() {
+        let inputs = ThreatDefenceInputs;
+        let bytes = reference_net();
+        let net = QuantisedPolicy::from_bytes(&bytes, inputs.num_inputs()).unwrap();
+        let (pos, castling) = position();
+
+        let pruned = net.pruned(&[1]);
+        assert_eq!(pruned.hidden_size(), 2);
+        assert_eq!(pruned.l0(&inputs, &pos), [0.25, 1.0]);
+        assert_eq!(pruned.hidden(&inputs, &pos), [0.25]);
+
+        // a1a2 only used the first pair, so loses its weight but not its bias
+        let indices = move_indices(&pos, &castling);
+        let (l1w, l1b) = (pruned.tensors()[2].1, pruned.tensors()[3].1);
+        let weight = |uci| l1w[indices.iter().find(|(name, _)| name == uci).unwrap().1];
+
+        assert_eq!([weight("a1a2"), weight("a1b1"), weight("a1b2")], [0.0, -0.5, 0.0]);
+        assert_eq!(l1b, net.tensors()[3].1);
+
+        // keeping every pair changes nothing
+        assert_eq!(net.pruned(&[0, 1]).to_bytes(), bytes);
+
+        let reloaded = QuantisedPolicy::from_bytes(&pruned.to_bytes(), inputs.num_inputs()).unwrap();
+        assert_eq!(reloaded.tensors(), pruned.tensors());
+    }
+}
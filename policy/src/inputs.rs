@@ -1,6 +1,10 @@
 use montyformat::chess::{Attacks, Flag, Move, Piece, Position, Side};
 use montyformat::chess::consts::{IN_BETWEEN, LINE_THROUGH, Rank};
 
+mod features;
+
+pub use features::{KingBucketedInputs, PolicyInputs, ThreatDefenceInputs};
+
 macro_rules! pop_lsb {
     ($sq:ident, $bb:expr) => {
         let $sq = ($bb).trailing_zeros() as usize;
@@ -13,8 +17,6 @@ pub trait See {
 }
 
 pub const MAX_MOVES: usize = 64;
-pub const INPUT_SIZE: usize = 768 * 4;
-pub const MAX_ACTIVE_BASE: usize = 32;
 pub const NUM_MOVES_INDICES: usize = 2 * FROM_TO;
 
 const FROM_TO: usize = OFFSETS[5][64] + PROMOS + 2 + 8;
@@ -50,58 +52,6 @@ pub fn map_move_to_index(pos: &Position, mov: Move) -> usize {
     FROM_TO * good_see + idx
 }
 
-pub fn map_base_inputs<F: FnMut(usize)>(pos: &Position, mut f: F) {
-    let vert = if pos.stm() == Side::BLACK { 56 } else { 0 };
-    let hori = if pos.king_index() % 8 > 3 { 7 } else { 0 };
-    let flip = vert ^ hori;
-
-    let threats = pos.threats_by(pos.stm() ^ 1);
-    let defences = pos.threats_by(pos.stm());
-
-    for piece in Piece::PAWN..=Piece::KING {
-        let pc = 64 * (piece - 2);
-
-        let mut our_bb = pos.piece(piece) & pos.piece(pos.stm());
-        let mut opp_bb = pos.piece(piece) & pos.piece(pos.stm() ^ 1);
-
-        while our_bb > 0 {
-            let sq = our_bb.trailing_zeros() as usize;
-            let mut feat = pc + (sq ^ flip);
-
-            let bit = 1 << sq;
-            if threats & bit > 0 {
-                feat += 768;
-            }
-
-            if defences & bit > 0 {
-                feat += 768 * 2;
-            }
-
-            f(feat);
-
-            our_bb &= our_bb - 1;
-        }
-
-        while opp_bb > 0 {
-            let sq = opp_bb.trailing_zeros() as usize;
-            let mut feat = 384 + pc + (sq ^ flip);
-
-            let bit = 1 << sq;
-            if threats & bit > 0 {
-                feat += 768;
-            }
-
-            if defences & bit > 0 {
-                feat += 768 * 2;
-            }
-
-            f(feat);
-
-            opp_bb &= opp_bb - 1;
-        }
-    }
-}
-
 const SEE_VALS: [i32; 8] = [0, 0, 100, 450, 450, 650, 1250, 0];
 
 impl See for Position {
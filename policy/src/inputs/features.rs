@@ -0,0 +1,200 @@
+use montyformat::chess::{Piece, Position, Side};
+
+/// A sparse feature set for the policy network's `l0` layer.
+pub trait PolicyInputs: Clone + Send + Sync + 'static {
+    fn num_inputs(&self) -> usize;
+
+    fn max_active(&self) -> usize;
+
+    fn map_features<F: FnMut(usize)>(&self, pos: &Position, f: F);
+
+    fn shorthand(&self) -> String;
+
+    fn description(&self) -> String;
+}
+
+/// Piece-square features from the side-to-move's perspective, horizontally
+/// mirrored on king file, with a threat bit and a defence bit per piece.
+#[derive(Clone, Copy, Default)]
+pub struct ThreatDefenceInputs;
+
+impl PolicyInputs for ThreatDefenceInputs {
+    fn num_inputs(&self) -> usize {
+        768 * 4
+    }
+
+    fn max_active(&self) -> usize {
+        32
+    }
+
+    fn map_features<F: FnMut(usize)>(&self, pos: &Position, f: F) {
+        map_threat_defence(pos, f);
+    }
+
+    fn shorthand(&self) -> String {
+        format!("{}", self.num_inputs())
+    }
+
+    fn description(&self) -> String {
+        "Piece-square inputs with threat and defence bits, mirrored on king file".to_string()
+    }
+}
+
+/// `ThreatDefenceInputs` duplicated per king bucket, where the bucket is
+/// looked up from the side-to-move's (oriented and mirrored) king square.
+#[derive(Clone, Copy)]
+pub struct KingBucketedInputs {
+    buckets: [usize; 64],
+    num_buckets: usize,
+}
+
+impl Default for KingBucketedInputs {
+    fn default() -> Self {
+        #[rustfmt::skip]
+        let buckets = [
+            0, 0, 1, 1, 1, 1, 0, 0,
+            2, 2, 2, 2, 2, 2, 2, 2,
+            3, 3, 3, 3, 3, 3, 3, 3,
+            3, 3, 3, 3, 3, 3, 3, 3,
+            3, 3, 3, 3, 3, 3, 3, 3,
+            3, 3, 3, 3, 3, 3, 3, 3,
+            3, 3, 3, 3, 3, 3, 3, 3,
+            3, 3, 3, 3, 3, 3, 3, 3,
+        ];
+
+        Self::new(buckets)
+    }
+}
+
+impl KingBucketedInputs {
+    pub fn new(buckets: [usize; 64]) -> Self {
+        Self { buckets, num_buckets: montytrain_common::buckets::count(&buckets) }
+    }
+
+    pub fn num_buckets(&self) -> usize {
+        self.num_buckets
+    }
+
+    pub fn bucket(&self, pos: &Position) -> usize {
+        self.buckets[pos.king_index() ^ orientation(pos)]
+    }
+}
+
+impl PolicyInputs for KingBucketedInputs {
+    fn num_inputs(&self) -> usize {
+        768 * 4 * self.num_buckets
+    }
+
+    fn max_active(&self) -> usize {
+        32
+    }
+
+    fn map_features<F: FnMut(usize)>(&self, pos: &Position, mut f: F) {
+        let offset = 768 * 4 * self.bucket(pos);
+        map_threat_defence(pos, |feat| f(offset + feat));
+    }
+
+    fn shorthand(&self) -> String {
+        format!("{}x{}hm", 768 * 4, self.num_buckets)
+    }
+
+    fn description(&self) -> String {
+        format!("King-bucketed ({}) piece-square inputs with threat and defence bits", self.num_buckets)
+    }
+}
+
+fn orientation(pos: &Position) -> usize {
+    let vert = if pos.stm() == Side::BLACK { 56 } else { 0 };
+    let hori = if pos.king_index() % 8 > 3 { 7 } else { 0 };
+    vert ^ hori
+}
+
+fn map_threat_defence<F: FnMut(usize)>(pos: &Position, mut f: F) {
+    let flip = orientation(pos);
+
+    let threats = pos.threats_by(pos.stm() ^ 1);
+    let defences = pos.threats_by(pos.stm());
+
+    for piece in Piece::PAWN..=Piece::KING {
+        let pc = 64 * (piece - 2);
+
+        let mut our_bb = pos.piece(piece) & pos.piece(pos.stm());
+        let mut opp_bb = pos.piece(piece) & pos.piece(pos.stm() ^ 1);
+
+        while our_bb > 0 {
+            let sq = our_bb.trailing_zeros() as usize;
+            let mut feat = pc + (sq ^ flip);
+
+            let bit = 1 << sq;
+            if threats & bit > 0 {
+                feat += 768;
+            }
+
+            if defences & bit > 0 {
+                feat += 768 * 2;
+            }
+
+            f(feat);
+
+            our_bb &= our_bb - 1;
+        }
+
+        while opp_bb > 0 {
+            let sq = opp_bb.trailing_zeros() as usize;
+            let mut feat = 384 + pc + (sq ^ flip);
+
+            let bit = 1 << sq;
+            if threats & bit > 0 {
+                feat += 768;
+            }
+
+            if defences & bit > 0 {
+                feat += 768 * 2;
+            }
+
+            f(feat);
+
+            opp_bb &= opp_bb - 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use montyformat::chess::Castling;
+
+    use super::*;
+
+    fn features(inputs: &impl PolicyInputs, fen: &str) -> Vec<usize> {
+        let mut castling = Castling::default();
+        let pos = Position::parse_fen(fen, &mut castling);
+
+        let mut feats = Vec::new();
+        inputs.map_features(&pos, |feat| feats.push(feat));
+        feats.sort();
+        feats
+    }
+
+    #[test]
+    fn king_buckets_follow_the_oriented_king() {
+        let inputs = KingBucketedInputs::default();
+        assert_eq!(inputs.num_buckets(), 4);
+        assert_eq!(inputs.num_inputs(), 768 * 4 * 4);
+
+        let king = 64 * (Piece::KING - 2);
+        let bucket = 768 * 4 * 2;
+
+        // a king on e2 is mirrored onto d2, and the same position with black
+        // to move is oriented onto it too
+        for fen in ["8/8/8/8/8/8/4K3/k7 w - - 0 1", "K7/4k3/8/8/8/8/8/8 b - - 0 1"] {
+            assert_eq!(features(&inputs, fen), [bucket + king + 11, bucket + 384 + king + 7], "{fen}");
+        }
+
+        // no mirroring with the king on the queenside
+        assert_eq!(features(&inputs, "8/8/8/8/8/8/1K6/7k w - - 0 1"), [bucket + king + 9, bucket + 384 + king + 7]);
+
+        // back rank corners and centre use the first two buckets
+        assert_eq!(features(&inputs, "7k/8/8/8/8/8/8/6K1 w - - 0 1"), [king + 1, 384 + king + 56]);
+        assert_eq!(features(&inputs, "7k/8/8/8/8/8/8/4K3 w - - 0 1")[0], 768 * 4 + king + 3);
+    }
+}
@@ -0,0 +1,6 @@
+pub mod config;
+pub mod data;
+pub mod infer;
+pub mod inputs;
+pub mod lr;
+pub mod model;
@@ -0,0 +1,211 @@
+use std::f32::consts::PI;
+
+/// Parameters shared by the schedules, each only reads the ones it needs.
+#[derive(Clone, Copy, Debug)]
+pub struct LrParams {
+    pub initial_lr: f32,
+    pub final_lr: f32,
+    /// Superbatches spent warming up to `initial_lr`.
+    pub warmup: usize,
+    /// Superbatches between each multiplication by `gamma` in step decay.
+    pub step: usize,
+    pub gamma: f32,
+}
+
+/// Learning rate as a function of the (1-based) superbatch. Schedules that
+/// decay train the first superbatch after warmup at `initial_lr` and the
+/// last at `final_lr`.
+#[derive(Clone, Copy, Debug)]
+pub enum LrSchedule {
+    Constant {
+        lr: f32,
+    },
+    /// Exponential decay from `initial_lr` towards `final_lr`, matching
+    /// bullet's `ExponentialDecayLR` that the trainer originally used. Unlike
+    /// the other decaying schedules, `initial_lr` is the rate at superbatch 0,
+    /// so the first superbatch already trains one step below it.
+    Exponential {
+        initial_lr: f32,
+        final_lr: f32,
+        end_superbatch: usize,
+    },
+    /// Linear from `initial_lr / warmup` to `initial_lr`, constant afterwards.
+    LinearWarmup {
+        lr: f32,
+        warmup: usize,
+    },
+    Cosine {
+        initial_lr: f32,
+        final_lr: f32,
+        end_superbatch: usize,
+    },
+    /// `initial_lr` multiplied by `gamma` every `step` superbatches.
+    StepDecay {
+        initial_lr: f32,
+        gamma: f32,
+        step: usize,
+    },
+    WarmupCosine {
+        initial_lr: f32,
+        final_lr: f32,
+        warmup: usize,
+        end_superbatch: usize,
+    },
+}
+
+impl LrSchedule {
+    pub const NAMES: [&'static str; 6] =
+        ["constant", "exponential", "linear-warmup", "cosine", "step", "warmup-cosine"];
+
+    pub fn from_name(name: &str, params: &LrParams, end_superbatch: usize) -> Option<Self> {
+        let LrParams { initial_lr, final_lr, warmup, step, gamma } = *params;
+
+        Some(match name {
+            "constant" => Self::Constant { lr: initial_lr },
+            "exponential" => Self::Exponential { initial_lr, final_lr, end_superbatch },
+            "linear-warmup" => Self::LinearWarmup { lr: initial_lr, warmup },
+            "cosine" => Self::Cosine { initial_lr, final_lr, end_superbatch },
+            "step" => Self::StepDecay { initial_lr, gamma, step },
+            "warmup-cosine" => Self::WarmupCosine { initial_lr, final_lr, warmup, end_superbatch },
+            _ => return None,
+        })
+    }
+
+    pub fn lr(&self, superbatch: usize) -> f32 {
+        match *self {
+            Self::Constant { lr } => lr,
+            Self::Exponential { initial_lr, final_lr, end_superbatch } => {
+                if superbatch >= end_superbatch {
+                    return final_lr;
+                }
+
+                let lambda = superbatch as f32 / end_superbatch as f32;
+                initial_lr * (final_lr / initial_lr).powf(lambda)
+            }
+            Self::LinearWarmup { lr, warmup } => lr * warmup_factor(superbatch, warmup),
+            Self::Cosine { initial_lr, final_lr, end_superbatch } => {
+                cosine(initial_lr, final_lr, progress(superbatch, 1, end_superbatch))
+            }
+            Self::StepDecay { initial_lr, gamma, step } => {
+                initial_lr * gamma.powi((superbatch.saturating_sub(1) / step.max(1)) as i32)
+            }
+            Self::WarmupCosine { initial_lr, final_lr, warmup, end_superbatch } => {
+                if superbatch < warmup {
+                    initial_lr * warmup_factor(superbatch, warmup)
+                } else {
+                    cosine(initial_lr, final_lr, progress(superbatch, warmup.max(1), end_superbatch))
+                }
+            }
+        }
+    }
+}
+
+fn warmup_factor(superbatch: usize, warmup: usize) -> f32 {
+    if warmup == 0 {
+        1.0
+    } else {
+        superbatch.clamp(1, warmup) as f32 / warmup as f32
+    }
+}
+
+/// Fraction of the way from `start` to `end`, clamped to `[0, 1]`.
+fn progress(superbatch: usize, start: usize, end: usize) -> f32 {
+    if end <= start {
+        return 1.0;
+    }
+
+    (superbatch.saturating_sub(start) as f32 / (end - start) as f32).min(1.0)
+}
+
+fn cosine(initial_lr: f32, final_lr: f32, progress: f32) -> f32 {
+    final_lr + 0.5 * (initial_lr - final_lr) * (1.0 + (PI * progress).cos())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARAMS: LrParams = LrParams { initial_lr: 0.001, final_lr: 0.00001, warmup: 10, step: 100, gamma: 0.1 };
+    const END: usize = 800;
+
+    fn schedule(name: &str) -> LrSchedule {
+        LrSchedule::from_name(name, &PARAMS, END).unwrap()
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() <= 1e-6 * b.abs().max(1e-6), "{a} != {b}");
+    }
+
+    #[test]
+    fn every_name_parses() {
+        for name in LrSchedule::NAMES {
+            assert!(LrSchedule::from_name(name, &PARAMS, END).is_some(), "{name}");
+        }
+
+        assert!(LrSchedule::from_name("linear", &PARAMS, END).is_none());
+    }
+
+    #[test]
+    fn constant() {
+        let schedule = schedule("constant");
+        assert_close(schedule.lr(1), 0.001);
+        assert_close(schedule.lr(END), 0.001);
+        assert_close(schedule.lr(END + 1), 0.001);
+    }
+
+    #[test]
+    fn exponential() {
+        let schedule = schedule("exponential");
+        assert_close(schedule.lr(1), 0.001 * 0.01f32.powf(1.0 / END as f32));
+        assert!(schedule.lr(1) < 0.001);
+        assert_close(schedule.lr(END / 2), 0.0001);
+        assert_close(schedule.lr(END), 0.00001);
+        assert_close(schedule.lr(END + 1), 0.00001);
+    }
+
+    #[test]
+    fn linear_warmup() {
+        let schedule = schedule("linear-warmup");
+        assert_close(schedule.lr(0), 0.0001);
+        assert_close(schedule.lr(1), 0.0001);
+        assert_close(schedule.lr(5), 0.0005);
+        assert_close(schedule.lr(10), 0.001);
+        assert_close(schedule.lr(END), 0.001);
+    }
+
+    #[test]
+    fn cosine() {
+        let schedule = schedule("cosine");
+        assert_close(schedule.lr(1), 0.001);
+        assert_close(schedule.lr(END), 0.00001);
+        assert_close(schedule.lr(END + 1), 0.00001);
+        assert!(schedule.lr(2) < schedule.lr(1));
+    }
+
+    #[test]
+    fn step_decay() {
+        let schedule = schedule("step");
+        assert_close(schedule.lr(1), 0.001);
+        assert_close(schedule.lr(100), 0.001);
+        assert_close(schedule.lr(101), 0.0001);
+        assert_close(schedule.lr(201), 0.00001);
+    }
+
+    #[test]
+    fn warmup_cosine() {
+        let schedule = schedule("warmup-cosine");
+        assert_close(schedule.lr(1), 0.0001);
+        assert_close(schedule.lr(9), 0.0009);
+        assert_close(schedule.lr(10), 0.001);
+        assert_close(schedule.lr(END), 0.00001);
+        assert_close(schedule.lr(END + 1), 0.00001);
+    }
+
+    #[test]
+    fn degenerate_lengths() {
+        let params = LrParams { warmup: 0, step: 0, ..PARAMS };
+        assert_close(LrSchedule::from_name("linear-warmup", &params, END).unwrap().lr(1), 0.001);
+        assert_close(LrSchedule::from_name("step", &params, END).unwrap().lr(5), 0.001 * 0.1f32.powi(4));
+        assert_close(LrSchedule::from_name("cosine", &params, 1).unwrap().lr(1), 0.00001);
+    }
+}
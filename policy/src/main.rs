@@ -1,6 +1,7 @@
-pub mod data;
-pub mod inputs;
-pub mod model;
+use std::{
+    cell::{Cell, RefCell},
+    path::Path,
+};
 
 use bullet_core::{
     device::Device,
@@ -14,58 +15,174 @@ use bullet_core::{
     },
 };
 use bullet_cuda_backend::CudaDevice;
+use montyformat::MontyFormat;
+use montytrain_common::{
+    checkpoints::{Checkpoints, Retention},
+    cli::Cli,
+    interrupt::{self, Progress},
+    runlog::{Entry, RunLog},
+    sample,
+};
+use policy::{config, data::MontyDataLoader, infer::QuantisedPolicy, inputs::PolicyInputs, model};
 
-use data::MontyDataLoader;
+/// Validation games split off by `interleave --validation`. Checkpoints are
+/// only ranked by recency if it does not exist.
+const VALIDATION_SET: &str = "data/validation.binpack";
+/// Positions sampled from the validation set and evaluated on the CPU at each save.
+const VALIDATION_POSITIONS: usize = 16_384;
+
+type PolicyOptimiser = Optimiser<CudaDevice, AdamW<CudaDevice>>;
 
 fn main() {
-    let hl = 16384;
-    let dataloader = MontyDataLoader::new("/home/privateclient/monty_value_training/interleaved.binpack", 96000, 8);
+    let args = Cli::new("policy", "Trains the policy network.")
+        .optional("resume", "Checkpoint folder to continue from, such as one written when interrupted")
+        .default(
+            "lr-schedule",
+            config::LR_SCHEDULE,
+            "Learning rate schedule: constant, exponential, linear-warmup, cosine, step or warmup-cosine",
+        )
+        .parse();
+
+    let end_superbatch = 800;
+    let lr_schedule = config::lr_schedule(args.get("lr-schedule"), end_superbatch);
+
+    let hl = config::HIDDEN_SIZE;
+    let feature_set = config::inputs();
+    let dataloader =
+        MontyDataLoader::new("/home/privateclient/monty_value_training/interleaved.binpack", 96000, 8, feature_set);
 
     let device = CudaDevice::new(0).unwrap();
 
-    let (graph, node) = model::make(device, hl);
+    let (graph, node) = model::make(device, hl, &feature_set);
 
     let params = AdamWParams { decay: 0.01, beta1: 0.9, beta2: 0.999, min_weight: -0.99, max_weight: 0.99 };
     let optimiser = Optimiser::<_, AdamW<_>>::new(graph, params).unwrap();
 
     let mut trainer = Trainer { optimiser, state: () };
+    let batches_per_superbatch = 6104;
+
+    let start_superbatch = match args.get_opt("resume") {
+        Some(dir) => {
+            trainer.optimiser.load_from_checkpoint(dir).unwrap();
+            let start = Progress::read(dir).unwrap().next_superbatch;
+            println!("Resuming from {dir} at superbatch {start}");
+            start
+        }
+        None => 1,
+    };
 
     let save_rate = 40;
-    let end_superbatch = 800;
-    let initial_lr = 0.001;
-    let final_lr = 0.00001;
-
-    let steps = TrainingSteps { batch_size: 16384, batches_per_superbatch: 6104, start_superbatch: 1, end_superbatch };
-
-    let schedule = TrainingSchedule {
-        steps,
-        log_rate: 64,
-        lr_schedule: Box::new(|_, sb| {
-            if sb >= end_superbatch {
-                return final_lr;
-            }
-
-            let lambda = sb as f32 / end_superbatch as f32;
-            initial_lr * (final_lr / initial_lr).powf(lambda)
-        }),
+    let retention = Retention { keep_last: 2, keep_best: 3 };
+    let log_rate = 64;
+
+    let steps = TrainingSteps { batch_size: 16384, batches_per_superbatch, start_superbatch, end_superbatch };
+
+    let schedule = TrainingSchedule { steps, log_rate, lr_schedule: Box::new(move |_, sb| lr_schedule.lr(sb)) };
+
+    // mean loss of each superbatch and of each `log_rate` batches, shared between the two callbacks
+    let superbatch_loss = Cell::new((0.0f32, 0usize));
+    let window_loss = Cell::new((0.0f32, 0usize));
+    let log = RefCell::new(RunLog::open("checkpoints").unwrap());
+    let checkpoints = RefCell::new(match args.get_opt("resume") {
+        Some(_) => Checkpoints::open("checkpoints", "policy", retention).unwrap(),
+        None => Checkpoints::new("checkpoints", retention),
+    });
+
+    interrupt::install();
+
+    let validation = if Path::new(VALIDATION_SET).is_file() {
+        sample::from_binpack::<MontyFormat>(VALIDATION_SET, VALIDATION_POSITIONS, 0)
+    } else {
+        println!("No validation set at {VALIDATION_SET:#?}");
+        Vec::new()
     };
 
     trainer
         .train_custom(
             schedule,
             dataloader,
-            |_, _, _, _| {},
+            |trainer, superbatch, batch, error| {
+                for loss in [&superbatch_loss, &window_loss] {
+                    let (total, batches) = loss.get();
+                    loss.set((total + error, batches + 1));
+                }
+
+                if batch % log_rate == 0 {
+                    let (total, batches) = window_loss.take();
+                    let mut log = log.borrow_mut();
+                    let positions_per_sec = log.speed(batches * steps.batch_size);
+
+                    let entry = Entry {
+                        superbatch,
+                        batch,
+                        loss: Some(total / batches as f32),
+                        lr: lr_schedule.lr(superbatch),
+                        positions_per_sec: Some(positions_per_sec),
+                        ..Default::default()
+                    };
+
+                    log.write(&entry).unwrap();
+                }
+
+                if interrupt::requested() {
+                    let dir = format!("checkpoints/policy-{superbatch}-interrupted");
+                    save_checkpoint(&trainer.optimiser, &feature_set, &dir, Progress::interrupted(superbatch));
+                    checkpoints.borrow_mut().add_interrupted(superbatch, &dir).unwrap();
+                    println!("Saved {dir}, continue with --resume {dir}");
+                    std::process::exit(0);
+                }
+            },
             |trainer, superbatch| {
+                let (total, batches) = superbatch_loss.take();
+                println!(
+                    "superbatch {superbatch} | loss {:.5} | lr {:.3e}",
+                    total / batches.max(1) as f32,
+                    lr_schedule.lr(superbatch)
+                );
+
                 if superbatch % save_rate == 0 || superbatch == steps.end_superbatch {
                     println!("Saving Checkpoint");
                     let dir = format!("checkpoints/policy-{superbatch}");
-                    let _ = std::fs::create_dir(&dir);
-                    trainer.optimiser.write_to_checkpoint(&dir).unwrap();
-                    model::save_quantised(&trainer.optimiser.graph, &format!("{dir}/quantised.bin")).unwrap();
+                    save_checkpoint(&trainer.optimiser, &feature_set, &dir, Progress::finished(superbatch));
+
+                    let mut validation_loss = None;
+
+                    if !validation.is_empty() {
+                        let net = QuantisedPolicy::from_file(&dir, feature_set.num_inputs()).unwrap();
+                        let (loss, accuracy) = net.validate(&feature_set, &validation);
+                        println!("validation loss {loss:.5} | accuracy {:.2}%", 100.0 * accuracy);
+
+                        let entry = Entry {
+                            superbatch,
+                            batch: steps.batches_per_superbatch,
+                            lr: lr_schedule.lr(superbatch),
+                            validation_loss: Some(loss),
+                            validation_accuracy: Some(accuracy),
+                            ..Default::default()
+                        };
+
+                        log.borrow_mut().write(&entry).unwrap();
+                        validation_loss = Some(loss);
+                    }
+
+                    let is_final = superbatch == steps.end_superbatch;
+                    checkpoints.borrow_mut().add(superbatch, &dir, validation_loss, is_final).unwrap();
                 }
             },
         )
         .unwrap();
 
-    model::eval(&mut trainer.optimiser.graph, node, "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
-}
\ No newline at end of file
+    model::eval(
+        &mut trainer.optimiser.graph,
+        node,
+        &feature_set,
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    );
+}
+
+fn save_checkpoint(optimiser: &PolicyOptimiser, feature_set: &config::Inputs, dir: &str, progress: Progress) {
+    let _ = std::fs::create_dir(dir);
+    optimiser.write_to_checkpoint(dir).unwrap();
+    model::save_quantised(&optimiser.graph, feature_set, &format!("{dir}/quantised.bin")).unwrap();
+    progress.write(dir).unwrap();
+}
@@ -12,17 +12,19 @@ use montyformat::chess::{Castling, Move, Position};
 
 use crate::{
     data::{loader::prepare, reader::DecompressedData},
-    inputs::{INPUT_SIZE, MAX_ACTIVE_BASE, MAX_MOVES, NUM_MOVES_INDICES},
+    inputs::{PolicyInputs, MAX_MOVES, NUM_MOVES_INDICES},
 };
 
-pub fn make(device: CudaDevice, hl: usize) -> (Graph<CudaDevice>, NodeId) {
+pub fn make<I: PolicyInputs>(device: CudaDevice, hl: usize, feature_set: &I) -> (Graph<CudaDevice>, NodeId) {
     let builder = GraphBuilder::default();
 
-    let inputs = builder.new_sparse_input("inputs", Shape::new(INPUT_SIZE, 1), MAX_ACTIVE_BASE);
+    let num_inputs = feature_set.num_inputs();
+
+    let inputs = builder.new_sparse_input("inputs", Shape::new(num_inputs, 1), feature_set.max_active());
     let targets = builder.new_dense_input("targets", Shape::new(MAX_MOVES, 1));
     let moves = builder.new_sparse_input("moves", Shape::new(NUM_MOVES_INDICES, 1), MAX_MOVES);
 
-    let l0 = builder.new_affine("l0", INPUT_SIZE, hl);
+    let l0 = builder.new_affine("l0", num_inputs, hl);
     let l1 = builder.new_affine("l1", hl / 2, NUM_MOVES_INDICES);
 
     let hl = l0.forward(inputs).crelu().pairwise_mul();
@@ -37,7 +39,7 @@ pub fn make(device: CudaDevice, hl: usize) -> (Graph<CudaDevice>, NodeId) {
     (builder.build(device), node)
 }
 
-pub fn eval(graph: &mut Graph<CudaDevice>, node: NodeId, fen: &str) {
+pub fn eval<I: PolicyInputs>(graph: &mut Graph<CudaDevice>, node: NodeId, feature_set: &I, fen: &str) {
     let mut castling = Castling::default();
     let pos = Position::parse_fen(fen, &mut castling);
 
@@ -51,7 +53,7 @@ pub fn eval(graph: &mut Graph<CudaDevice>, node: NodeId, fen: &str) {
 
     let point = DecompressedData { pos, castling, moves, num };
 
-    let data = prepare(&[point], 1);
+    let data = prepare(feature_set, &[point], 1);
 
     let mut on_device = PreparedBatchDevice::new(graph.device(), &data).unwrap();
 
@@ -68,7 +70,7 @@ pub fn eval(graph: &mut Graph<CudaDevice>, node: NodeId, fen: &str) {
     }
 }
 
-pub fn save_quantised(graph: &Graph<CudaDevice>, path: &str) -> std::io::Result<()> {
+pub fn save_quantised<I: PolicyInputs>(graph: &Graph<CudaDevice>, feature_set: &I, path: &str) -> std::io::Result<()> {
     use std::io::Write;
 
     let mut file = std::fs::File::create(path).unwrap();
@@ -78,6 +80,10 @@ pub fn save_quantised(graph: &Graph<CudaDevice>, path: &str) -> std::io::Result<
     for id in ["l0w", "l0b", "l1w", "l1b"] {
         let vals = graph.get_weights(id).get_dense_vals().unwrap();
 
+        if id == "l0w" {
+            assert_eq!(vals.len() % feature_set.num_inputs(), 0, "Feature set does not match the network!");
+        }
+
         for x in vals {
             let q = (x * 128.0).round() as i8;
             assert_eq!((x * 128.0).round(), f32::from(q));
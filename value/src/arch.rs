@@ -4,67 +4,151 @@ use bullet::{
         Activation, ExecutionContext, Graph, InitSettings, NetworkBuilder, Node, Shape,
     },
     trainer::{
-        default::{inputs::SparseInputType, outputs, Trainer},
+        default::{inputs::SparseInputType, outputs::OutputBuckets, Trainer},
         save::{Layout, QuantTarget, SavedFormat},
     },
 };
 
-pub fn make_trainer<T: Default + SparseInputType>(l1: usize) -> Trainer<AdamWOptimiser, T, outputs::Single> {
-    let inputs = T::default();
+/// Number of output logits (WDL).
+pub const OUTPUTS: usize = 3;
+
+/// A hidden layer following the `l0` pairwise product.
+#[derive(Clone, Copy)]
+pub struct Layer {
+    pub size: usize,
+    pub activation: Activation,
+    pub quant: QuantTarget,
+}
+
+/// Describes the network as
+/// `inputs -> l0 (CReLU, pairwise) -> hidden.. -> OUTPUTS (+ pst)`,
+/// with every layer after `l0` selected by output bucket.
+#[derive(Clone)]
+pub struct ArchSpec {
+    pub l0: usize,
+    pub l0_quant: QuantTarget,
+    pub hidden: Vec<Layer>,
+    pub output_quant: QuantTarget,
+    pub pst: bool,
+}
+
+impl ArchSpec {
+    pub fn new(l0: usize) -> Self {
+        Self {
+            l0,
+            l0_quant: QuantTarget::I16(512),
+            hidden: vec![
+                Layer { size: 16, activation: Activation::SCReLU, quant: QuantTarget::I16(1024) },
+                Layer { size: 128, activation: Activation::SCReLU, quant: QuantTarget::Float },
+            ],
+            output_quant: QuantTarget::Float,
+            pst: true,
+        }
+    }
+
+    /// `(inputs, outputs)` of each affine layer `l0, l1, ..`, where the outputs
+    /// of every layer after `l0` are per output bucket.
+    pub fn layer_sizes(&self, inputs: usize) -> Vec<(usize, usize)> {
+        let mut sizes = vec![(inputs, self.l0)];
+        let mut prev = self.l0 / 2;
+
+        for layer in &self.hidden {
+            sizes.push((prev, layer.size));
+            prev = layer.size;
+        }
+
+        sizes.push((prev, OUTPUTS));
+        sizes
+    }
+
+    pub fn layer_quant(&self, idx: usize) -> QuantTarget {
+        match idx {
+            0 => self.l0_quant,
+            i if i <= self.hidden.len() => self.hidden[i - 1].quant,
+            _ => self.output_quant,
+        }
+    }
+
+    /// `l1` is stored transposed so that each output neuron's weights over the
+    /// (wide) pairwise product are contiguous; every other layer is stored as-is.
+    pub fn saved_formats(&self, inputs: usize, buckets: usize) -> Vec<SavedFormat> {
+        let mut formats = Vec::new();
+
+        if self.pst {
+            formats.push(SavedFormat::new("pst", QuantTarget::Float, Layout::Normal));
+        }
+
+        for (i, &(ins, outs)) in self.layer_sizes(inputs).iter().enumerate() {
+            let quant = self.layer_quant(i);
+            let outs = if i == 0 { outs } else { outs * buckets };
+            let layout = if i == 1 { Layout::Transposed(Shape::new(outs, ins)) } else { Layout::Normal };
+
+            formats.push(SavedFormat::new(&format!("l{i}w"), quant, layout));
+            formats.push(SavedFormat::new(&format!("l{i}b"), quant, Layout::Normal));
+        }
+
+        formats
+    }
+}
+
+pub fn make_trainer<T, O>(inputs: T, output_buckets: O, spec: &ArchSpec) -> Trainer<AdamWOptimiser, T, O>
+where
+    T: SparseInputType,
+    O: OutputBuckets<T::RequiredDataType>,
+{
     let num_inputs = inputs.num_inputs();
     let nnz = inputs.max_active();
+    let buckets = O::BUCKETS;
 
-    let (mut graph, output_node) = build_network(num_inputs, nnz, l1);
-
-    let sizes = [num_inputs, l1 / 2, 16, 128];
+    let (mut graph, output_node) = build_network(spec, num_inputs, nnz, buckets);
 
     // seed biases because huge input featureset can be weird
-    for (i, &size) in sizes.iter().enumerate() {
+    for (i, &(size, _)) in spec.layer_sizes(num_inputs).iter().enumerate() {
         graph.get_weights_mut(&format!("l{i}b")).seed_random(0.0, 1.0 / (size as f32).sqrt(), true).unwrap();
     }
 
-    Trainer::new(
-        graph,
-        output_node,
-        AdamWParams::default(),
-        inputs,
-        outputs::Single,
-        vec![
-            SavedFormat::new("pst", QuantTarget::Float, Layout::Normal),
-            SavedFormat::new("l0w", QuantTarget::I16(512), Layout::Normal),
-            SavedFormat::new("l0b", QuantTarget::I16(512), Layout::Normal),
-            SavedFormat::new("l1w", QuantTarget::I16(1024), Layout::Transposed(Shape::new(16, l1 / 2))),
-            SavedFormat::new("l1b", QuantTarget::I16(1024), Layout::Normal),
-            SavedFormat::new("l2w", QuantTarget::Float, Layout::Normal),
-            SavedFormat::new("l2b", QuantTarget::Float, Layout::Normal),
-            SavedFormat::new("l3w", QuantTarget::Float, Layout::Normal),
-            SavedFormat::new("l3b", QuantTarget::Float, Layout::Normal),
-        ],
-        false,
-    )
+    let saved_formats = spec.saved_formats(num_inputs, buckets);
+
+    Trainer::new(graph, output_node, AdamWParams::default(), inputs, output_buckets, saved_formats, false)
 }
 
-fn build_network(inputs: usize, nnz: usize, l1: usize) -> (Graph, Node) {
+fn build_network(spec: &ArchSpec, inputs: usize, nnz: usize, buckets: usize) -> (Graph, Node) {
     let builder = NetworkBuilder::default();
 
     // inputs
     let stm = builder.new_sparse_input("stm", Shape::new(inputs, 1), nnz);
-    let targets = builder.new_dense_input("targets", Shape::new(3, 1));
+    let targets = builder.new_dense_input("targets", Shape::new(OUTPUTS, 1));
+    let output_buckets = (buckets > 1).then(|| builder.new_sparse_input("buckets", Shape::new(buckets, 1), 1));
 
     // trainable weights
-    let pst = builder.new_weights("pst", Shape::new(3, inputs), InitSettings::Zeroed);
-    let l0 = builder.new_affine("l0", inputs, l1);
-    let l1 = builder.new_affine("l1", l1 / 2, 16);
-    let l2 = builder.new_affine("l2", 16, 128);
-    let l3 = builder.new_affine("l3", 128, 3);
+    let layers = spec
+        .layer_sizes(inputs)
+        .iter()
+        .enumerate()
+        .map(|(i, &(ins, outs))| builder.new_affine(&format!("l{i}"), ins, if i == 0 { outs } else { outs * buckets }))
+        .collect::<Vec<_>>();
 
     // inference
-    let mut out = l0.forward(stm).activate(Activation::CReLU);
+    let mut out = layers[0].forward(stm).activate(Activation::CReLU);
     out = out.pairwise_mul();
-    out = l1.forward(out).activate(Activation::SCReLU);
-    out = l2.forward(out).activate(Activation::SCReLU);
-    out = l3.forward(out);
-    out = out + pst.matmul(stm);
+
+    for (i, layer) in layers.iter().enumerate().skip(1) {
+        out = layer.forward(out);
+
+        if let Some(buckets) = output_buckets {
+            out = out.select(buckets);
+        }
+
+        if let Some(hidden) = spec.hidden.get(i - 1) {
+            out = out.activate(hidden.activation);
+        }
+    }
+
+    if spec.pst {
+        let pst = builder.new_weights("pst", Shape::new(OUTPUTS, inputs), InitSettings::Zeroed);
+        out = out + pst.matmul(stm);
+    }
+
     out.softmax_crossentropy_loss(targets);
 
     // graph, output node
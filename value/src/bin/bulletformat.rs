@@ -1,37 +1,117 @@
 use std::{
-    fs::File,
-    io::{BufReader, BufWriter, Cursor},
+    io::{Cursor, Write},
     sync::mpsc::{self, SyncSender},
     time::Instant,
 };
 
 use bullet::default::formats::{
     bulletformat::{BulletFormat, ChessBoard},
-    montyformat::{FastDeserialise, MontyValueFormat},
+    montyformat::{chess::Side, FastDeserialise, MontyValueFormat},
 };
+use montytrain_common::{
+    cli::{self, Cli, Matches},
+    rng::{self, Rand},
+};
+
+#[derive(Clone, Copy)]
+struct Filters {
+    /// Drop positions before this ply of the game.
+    skip_plies: usize,
+    /// Keep each remaining position with this probability.
+    sample_rate: f64,
+    /// Drop positions with |score| above this.
+    max_score: i16,
+    checks: bool,
+    captures: bool,
+    promotions: bool,
+    /// Drop positions in drawn games with |score| above this.
+    drawn_score_limit: Option<i16>,
+    /// Drop positions in decisive games whose score favours the losing side by more than this.
+    decisive_score_limit: Option<i16>,
+    seed: u64,
+}
+
+impl Filters {
+    fn from_args(args: &Matches) -> Self {
+        let filters = Self {
+            skip_plies: args.parse("skip-plies"),
+            sample_rate: args.parse("sample-rate"),
+            max_score: args.parse("max-score"),
+            checks: !args.flag("keep-checks"),
+            captures: !args.flag("keep-captures"),
+            promotions: args.flag("drop-promotions"),
+            drawn_score_limit: args.parse_opt("drawn-score-limit"),
+            decisive_score_limit: args.parse_opt("decisive-score-limit"),
+            seed: args.parse("seed"),
+        };
+
+        if !(0.0..=1.0).contains(&filters.sample_rate) {
+            cli::fail("--sample-rate must be in [0, 1]");
+        }
+
+        filters
+    }
+
+    fn print(&self) {
+        println!("Filters:");
+        println!(" - Skip Plies    : {}", self.skip_plies);
+        println!(" - Sample Rate   : {}", self.sample_rate);
+        println!(" - Max Score     : {}", self.max_score);
+        println!(" - Checks        : {}", self.checks);
+        println!(" - Captures      : {}", self.captures);
+        println!(" - Promotions    : {}", self.promotions);
+        println!(" - Drawn Limit   : {:?}", self.drawn_score_limit);
+        println!(" - Decisive Limit: {:?}", self.decisive_score_limit);
+        println!("---------------------");
+    }
+}
 
 #[derive(Clone, Copy, Default)]
 struct Stats {
     positions: usize,
     filtered: usize,
+    plies: usize,
+    sampled: usize,
     checks: usize,
     caps: usize,
+    promos: usize,
     scores: usize,
+    drawn: usize,
+    decisive: usize,
     games: usize,
 }
 
 fn main() {
-    let mut args = std::env::args();
-    args.next();
+    let args = Cli::new("bulletformat", "Converts MontyValueFormat games into filtered bulletformat positions.")
+        .required("input", "MontyValueFormat binpack to read")
+        .required("output", "bulletformat file to write")
+        .default("threads", "4", "Number of conversion threads")
+        .default("skip-plies", "0", "Drop positions before this ply of each game")
+        .default("sample-rate", "1.0", "Keep each remaining position with this probability")
+        .default("max-score", "4000", "Drop positions with an absolute score above this")
+        .flag("keep-checks", "Keep positions where the side to move is in check")
+        .flag("keep-captures", "Keep positions where the best move is a capture")
+        .flag("drop-promotions", "Drop positions where the best move is a promotion")
+        .optional("drawn-score-limit", "Drop positions of drawn games with an absolute score above this")
+        .optional("decisive-score-limit", "Drop positions of decisive games scored above this for the losing side")
+        .default("seed", "0", "Seed for position sampling")
+        .parse();
 
-    let inp_path = args.next().unwrap();
-    let out_path = args.next().unwrap();
-    let threads = args.next().unwrap().parse().unwrap();
+    let inp_path = args.get("input");
+    let out_path = args.get("output");
+    let threads: usize = args.parse("threads");
+    let filters = Filters::from_args(&args);
     let per_thread_batch_size = 8192 * 4;
     let batch_size = threads * per_thread_batch_size;
 
-    let mut reader = BufReader::new(File::open(inp_path).unwrap());
-    let mut writer = BufWriter::new(File::create(out_path).unwrap());
+    if threads == 0 {
+        cli::fail("--threads must be at least 1");
+    }
+
+    filters.print();
+
+    let mut reader = cli::open_input(inp_path);
+    let mut writer = cli::create_output(out_path, &[inp_path]);
 
     let timer = Instant::now();
 
@@ -55,27 +135,36 @@ fn main() {
         while let Ok(game_bytes) = receiver.recv() {
             game_buffer.push(game_bytes);
             if game_buffer.len() % batch_size == 0 {
-                convert_buffer(threads, &sender2, &game_buffer, &mut stats);
+                convert_buffer(threads, &sender2, &game_buffer, &mut stats, &filters);
                 report(&stats, &timer);
                 game_buffer.clear();
             }
         }
 
         if !game_buffer.is_empty() {
-            convert_buffer(threads, &sender2, &game_buffer, &mut stats);
+            convert_buffer(threads, &sender2, &game_buffer, &mut stats, &filters);
         }
 
         stats
     });
 
     while let Ok(buf) = receiver2.recv() {
-        ChessBoard::write_to_bin(&mut writer, &buf).unwrap();
+        ChessBoard::write_to_bin(&mut writer, &buf)
+            .unwrap_or_else(|err| cli::fail(format!("could not write to '{out_path}': {err}")));
     }
 
+    writer.flush().unwrap_or_else(|err| cli::fail(format!("could not write to '{out_path}': {err}")));
+
     report(&lock.join().unwrap(), &timer);
 }
 
-fn convert_buffer(threads: usize, sender: &SyncSender<Vec<ChessBoard>>, games: &[Vec<u8>], stats: &mut [Stats]) {
+fn convert_buffer(
+    threads: usize,
+    sender: &SyncSender<Vec<ChessBoard>>,
+    games: &[Vec<u8>],
+    stats: &mut [Stats],
+    filters: &Filters,
+) {
     let chunk_size = games.len().div_ceil(threads);
 
     std::thread::scope(|s| {
@@ -83,40 +172,78 @@ fn convert_buffer(threads: usize, sender: &SyncSender<Vec<ChessBoard>>, games: &
             let this_sender = sender.clone();
             s.spawn(move || {
                 for game_bytes in chunk {
-                    convert(&this_sender, game_bytes, sub_stats);
+                    convert(&this_sender, game_bytes, sub_stats, filters);
                 }
             });
         }
     });
 }
 
-fn convert(sender: &SyncSender<Vec<ChessBoard>>, game_bytes: &[u8], stats: &mut Stats) {
+fn convert(sender: &SyncSender<Vec<ChessBoard>>, game_bytes: &[u8], stats: &mut Stats, filters: &Filters) {
     let mut reader = Cursor::new(&game_bytes);
     let game = MontyValueFormat::deserialise_from(&mut reader, Vec::new()).unwrap();
 
+    // seeded from the game itself so that sampling does not depend on threading
+    let mut rng = Rand::new(filters.seed ^ rng::hash_bytes(game_bytes));
+
     let mut buf = Vec::new();
 
     let mut pos = game.startpos;
     let castling = &game.castling;
 
-    for result in game.moves {
+    for (ply, result) in game.moves.into_iter().enumerate() {
         let mut write = true;
 
-        if pos.in_check() {
+        if ply < filters.skip_plies {
+            write = false;
+            stats.plies += 1;
+        }
+
+        if filters.checks && pos.in_check() {
             write = false;
             stats.checks += 1;
         }
 
-        if result.best_move.is_capture() {
+        if filters.captures && result.best_move.is_capture() {
             write = false;
             stats.caps += 1;
         }
 
-        if result.score == i16::MIN || result.score.abs() > 4000 {
+        if filters.promotions && result.best_move.is_promo() {
+            write = false;
+            stats.promos += 1;
+        }
+
+        if result.score == i16::MIN || result.score.abs() > filters.max_score {
             write = false;
             stats.scores += 1;
         }
 
+        // scores are relative to the side to move, results to white
+        let stm_result = if pos.stm() == Side::BLACK { 1.0 - game.result } else { game.result };
+
+        if let Some(limit) = filters.drawn_score_limit {
+            if stm_result == 0.5 && result.score.saturating_abs() > limit {
+                write = false;
+                stats.drawn += 1;
+            }
+        }
+
+        if let Some(limit) = filters.decisive_score_limit {
+            let misleading =
+                (stm_result == 0.0 && result.score > limit) || (stm_result == 1.0 && result.score < -limit);
+
+            if misleading {
+                write = false;
+                stats.decisive += 1;
+            }
+        }
+
+        if write && filters.sample_rate < 1.0 && rng.float() >= filters.sample_rate {
+            write = false;
+            stats.sampled += 1;
+        }
+
         if write {
             buf.push(ChessBoard::from_raw(pos.bbs(), pos.stm(), result.score, game.result).unwrap());
         } else {
@@ -132,30 +259,37 @@ fn convert(sender: &SyncSender<Vec<ChessBoard>>, game_bytes: &[u8], stats: &mut
 }
 
 fn report(stats: &[Stats], timer: &Instant) {
-    let mut positions = 0;
-    let mut filtered = 0;
-    let mut checks = 0;
-    let mut caps = 0;
-    let mut scores = 0;
-    let mut games = 0;
+    let mut total = Stats::default();
 
     for sub_stats in stats {
-        positions += sub_stats.positions;
-        filtered += sub_stats.filtered;
-        checks += sub_stats.checks;
-        caps += sub_stats.caps;
-        scores += sub_stats.scores;
-        games += sub_stats.games;
+        total.positions += sub_stats.positions;
+        total.filtered += sub_stats.filtered;
+        total.plies += sub_stats.plies;
+        total.sampled += sub_stats.sampled;
+        total.checks += sub_stats.checks;
+        total.caps += sub_stats.caps;
+        total.promos += sub_stats.promos;
+        total.scores += sub_stats.scores;
+        total.drawn += sub_stats.drawn;
+        total.decisive += sub_stats.decisive;
+        total.games += sub_stats.games;
     }
 
+    let positions = total.positions;
+
     println!("Positions: {positions}");
-    println!("Games    : {games}");
-    println!("Game Len : {:.2}", positions as f64 / games as f64);
-    println!("Filtered : {filtered}");
-    println!(" - Checks  : {checks}");
-    println!(" - Captures: {caps}");
-    println!(" - Scores  : {scores}");
-    println!("Remaining: {}", positions - filtered);
+    println!("Games    : {}", total.games);
+    println!("Game Len : {:.2}", positions as f64 / total.games as f64);
+    println!("Filtered : {}", total.filtered);
+    println!(" - Opening Plies: {}", total.plies);
+    println!(" - Checks       : {}", total.checks);
+    println!(" - Captures     : {}", total.caps);
+    println!(" - Promotions   : {}", total.promos);
+    println!(" - Scores       : {}", total.scores);
+    println!(" - Drawn Games  : {}", total.drawn);
+    println!(" - Decisive     : {}", total.decisive);
+    println!(" - Sampled Out  : {}", total.sampled);
+    println!("Remaining: {}", positions - total.filtered);
     println!("Speed: {:.0}k/sec", (positions / 1000) as f64 / timer.elapsed().as_secs_f64());
     println!("---------------------");
 }
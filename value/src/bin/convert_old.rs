@@ -1,56 +1,52 @@
-use std::{
-    fs::File,
-    io::{self, BufReader, BufWriter, Read, Write},
+use std::io::Write;
+
+use montytrain_common::{
+    cli::{self, Cli},
+    progress::Progress,
 };
+use value::layout::{self, Version};
 
 fn main() {
-    let mut args = std::env::args();
-    args.next();
-
-    let inp_path = args.next().unwrap();
-    let out_path = args.next().unwrap();
+    let args = Cli::new("convert_old", "Converts value data from the old MontyValueFormat layout to the current one.")
+        .required("input", "Old-format binpack to read")
+        .optional("output", "Binpack to write, required without --check and rejected with it")
+        .flag("check", "Only detect the version and validate every game, without writing anything")
+        .parse();
 
-    let mut reader = BufReader::new(File::open(inp_path).unwrap());
-    let mut writer = BufWriter::new(File::create(out_path).unwrap());
+    let inp_path = args.get("input");
+    let check = args.flag("check");
 
-    let mut positions = 0usize;
-    let mut games = 0usize;
+    let mut reader = cli::open_input(inp_path);
 
-    while let Ok(pos) = convert_game(&mut reader, &mut writer) {
-        games += 1;
-        positions += pos;
+    let version = layout::detect(&mut reader).unwrap_or_else(|err| cli::fail(format!("'{inp_path}': {err}")));
+    println!("Detected {version:?} layout");
 
-        if games % (16384 * 8) == 0 {
-            println!("Converted {games} Games, {positions} Positions");
-        }
+    if version == Version::Current && !check {
+        cli::fail(format!("'{inp_path}' is already in the current layout"));
     }
 
-    println!("Converted {games} Games, {positions} Positions")
-}
-
-fn convert_game(reader: &mut BufReader<File>, writer: &mut BufWriter<File>) -> io::Result<usize> {
-    let mut buf = [0u8; 36];
-    reader.read_exact(&mut buf)?;
-    writer.write_all(&buf).unwrap();
-    writer.write_all(&[0; 2]).unwrap();
+    let mut writer = match (args.get_opt("output"), check) {
+        (Some(out_path), false) => Some((out_path, cli::create_output(out_path, &[inp_path]))),
+        (None, false) => cli::fail("--output is required unless --check is given"),
+        (Some(_), true) => cli::fail("--check does not write anything, so cannot be given with --output"),
+        (None, true) => None,
+    };
 
-    let mut buf = [0u8; 5];
-    reader.read_exact(&mut buf)?;
-    writer.write_all(&buf).unwrap();
+    let mut progress = Progress::counter(if check { "Checked" } else { "Converted" }, "Games", 16384 * 8);
 
-    let mut positions = 0;
+    let summary = layout::convert(&mut reader, version, writer.as_mut().map(|(_, writer)| writer), &mut progress)
+        .unwrap_or_else(|err| cli::fail(format!("could not convert '{inp_path}': {err}")));
 
-    loop {
-        positions += 1;
+    if let Some((bytes, offset)) = summary.dropped {
+        println!("Dropped partial game of {bytes} bytes at byte {offset}");
+    }
 
-        let mut buf = [0; 4];
-        reader.read_exact(&mut buf).unwrap();
-        writer.write_all(&buf).unwrap();
+    let (games, positions) = (summary.games, summary.positions);
 
-        if buf == [0; 4] {
-            break;
-        }
+    if let Some((out_path, mut writer)) = writer {
+        writer.flush().unwrap_or_else(|err| cli::fail(format!("could not write to '{out_path}': {err}")));
+        println!("Converted {games} Games, {positions} Positions");
+    } else {
+        println!("Checked {games} Games, {positions} Positions");
     }
-
-    Ok(positions)
 }
@@ -0,0 +1,58 @@
+use bullet::{
+    default::formats::montyformat::MontyValueFormat,
+    trainer::default::{inputs::SparseInputType, outputs::OutputBuckets as _},
+};
+use montytrain_common::{
+    cli::{self, Cli},
+    neurons::NeuronStats,
+    pgn::STARTPOS,
+    sample,
+};
+use value::{
+    config::{self, OutputBuckets},
+    infer::{self, QuantisedNet},
+};
+
+fn main() {
+    let cli = Cli::new("dead-neurons", "Reports dead and saturated l0 neurons of a value net over a position sample.")
+        .required("net", "quantised.bin, or checkpoint folder containing it")
+        .optional("output", "Write a pruned quantised.bin without the pairs that were never active");
+
+    let args = sample::options(cli).parse();
+
+    if !sample::has_source(&args) {
+        cli::fail("no positions given, use --binpack, --fens or --fen");
+    }
+
+    let net_path = args.get("net");
+    let inputs = config::inputs();
+
+    let net = QuantisedNet::from_file(net_path, &config::spec(), inputs.num_inputs(), OutputBuckets::BUCKETS)
+        .unwrap_or_else(|err| cli::fail(format!("could not load '{net_path}': {err}")));
+
+    println!("Net: {net_path} (l0 {})", net.spec().l0);
+
+    let mut stats = NeuronStats::new(net.spec().l0);
+
+    for sample in sample::load::<MontyValueFormat>(&args, STARTPOS) {
+        let board = infer::board_from_fen(&sample.fen);
+        stats.record(&net.l0_activations(&infer::features(&inputs, &board)), net.l0_scale());
+    }
+
+    stats.print();
+
+    if let Some(out_path) = args.get_opt("output") {
+        let live = stats.live_pairs();
+
+        if live.is_empty() {
+            cli::fail("no pair was ever active, not writing an empty net");
+        }
+
+        let pruned = net.pruned(&live);
+
+        std::fs::write(out_path, pruned.to_bytes())
+            .unwrap_or_else(|err| cli::fail(format!("could not write '{out_path}': {err}")));
+
+        println!("Written {out_path} (l0 {})", pruned.spec().l0);
+    }
+}
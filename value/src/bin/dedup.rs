@@ -0,0 +1,9 @@
+use bullet::default::formats::montyformat::MontyValueFormat;
+use montytrain_common::dedup;
+
+fn main() {
+    dedup::main::<MontyValueFormat>(
+        "dedup",
+        "Reports and removes duplicated games and positions in MontyValueFormat binpacks.",
+    );
+}
@@ -0,0 +1,6 @@
+use bullet::default::formats::montyformat::MontyValueFormat;
+use montytrain_common::export;
+
+fn main() {
+    export::main::<MontyValueFormat>("export", "Exports MontyValueFormat positions as text lines.");
+}
@@ -0,0 +1,5 @@
+use montytrain_common::import;
+
+fn main() {
+    import::main("import", "Imports engine-annotated PGN games or EPD positions as MontyValueFormat games.");
+}
@@ -0,0 +1,97 @@
+use std::path::Path;
+
+use bullet::{
+    default::formats::montyformat::MontyValueFormat,
+    trainer::default::{inputs::SparseInputType, outputs::OutputBuckets as _},
+};
+use montytrain_common::{
+    cli::{self, Cli},
+    format::EVAL_SCALE,
+    netdiff,
+    pgn::STARTPOS,
+    sample, weights,
+};
+use value::{
+    config::{self, OutputBuckets},
+    infer::QuantisedNet,
+};
+
+/// Compares the weights of two value nets, and their WDL over a sample of
+/// positions.
+fn main() {
+    let cli = Cli::new("netdiff", "Compares two value checkpoints or quantised nets of the same architecture.")
+        .required("a", "First quantised.bin, or checkpoint folder containing it")
+        .required("b", "Second quantised.bin, or checkpoint folder containing it");
+
+    let args = sample::options(cli).parse();
+    let inputs = config::inputs();
+    let output_buckets = OutputBuckets::default();
+
+    let load = |path: &str| {
+        QuantisedNet::from_file(path, &config::spec(), inputs.num_inputs(), OutputBuckets::BUCKETS)
+            .unwrap_or_else(|err| cli::fail(format!("could not load '{path}': {err}")))
+    };
+
+    let (a, b) = (load(args.get("a")), load(args.get("b")));
+
+    if a.spec().l0 != b.spec().l0 {
+        cli::fail(format!("l0 sizes differ: {} vs {}", a.spec().l0, b.spec().l0));
+    }
+
+    // saturation only means something before quantisation, so compare the
+    // raw weights whenever both checkpoints still have them
+    let raw = ["a", "b"].map(|name| weights::find(Path::new(args.get(name))));
+
+    let (ta, tb) = match &raw {
+        [Some(ra), Some(rb)] => {
+            let read = |path: &Path| {
+                weights::read(path).unwrap_or_else(|err| cli::fail(format!("could not load raw weights: {err}")))
+            };
+            (read(ra), read(rb))
+        }
+        _ => (a.tensors(), b.tensors()),
+    };
+
+    println!("A: {} (l0 {})", args.get("a"), a.spec().l0);
+    println!("B: {} (l0 {})", args.get("b"), b.spec().l0);
+    println!("Weights: {}", if raw.iter().all(Option::is_some) { "raw" } else { "quantised" });
+    println!();
+
+    netdiff::print_header();
+    for (name, x) in &ta {
+        let Some((_, y)) = tb.iter().find(|(other, _)| other == name) else {
+            cli::fail(format!("'{name}' is missing from B"));
+        };
+
+        if x.len() != y.len() {
+            cli::fail(format!("'{name}' sizes differ: {} vs {}", x.len(), y.len()));
+        }
+
+        netdiff::print_tensor(name, x, y);
+    }
+
+    let samples = sample::load::<MontyValueFormat>(&args, STARTPOS);
+
+    let mut wdl = [0.0f64; 3];
+    let mut cp = 0.0f64;
+
+    for sample in &samples {
+        let ea = a.eval_fen(&inputs, &output_buckets, &sample.fen);
+        let eb = b.eval_fen(&inputs, &output_buckets, &sample.fen);
+
+        wdl[0] += f64::from((ea.win - eb.win).abs());
+        wdl[1] += f64::from((ea.draw - eb.draw).abs());
+        wdl[2] += f64::from((ea.loss - eb.loss).abs());
+        cp += f64::from((ea.centipawns(EVAL_SCALE) - eb.centipawns(EVAL_SCALE)).abs());
+    }
+
+    let n = samples.len().max(1) as f64;
+
+    println!();
+    println!("Positions     : {}", samples.len());
+    println!("Mean |dW|     : {:.5}", wdl[0] / n);
+    println!("Mean |dD|     : {:.5}", wdl[1] / n);
+    println!("Mean |dL|     : {:.5}", wdl[2] / n);
+    println!("Mean |dWDL|   : {:.5}", wdl.iter().sum::<f64>() / (3.0 * n));
+    println!("Mean |dEval|  : {:.1}", cp / n);
+}
@@ -0,0 +1,5 @@
+use montytrain_common::plot;
+
+fn main() {
+    plot::main("plot-log", "Renders the loss curves of value training logs to SVG.");
+}
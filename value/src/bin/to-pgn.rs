@@ -0,0 +1,6 @@
+use bullet::default::formats::montyformat::MontyValueFormat;
+use montytrain_common::pgn;
+
+fn main() {
+    pgn::main::<MontyValueFormat>("to-pgn", "Writes MontyValueFormat games as PGN, with scores as comments.");
+}
@@ -0,0 +1,61 @@
+use bullet::{
+    default::formats::montyformat::MontyValueFormat,
+    trainer::default::{inputs::SparseInputType, outputs::OutputBuckets as _},
+};
+use montytrain_common::{
+    cli::{self, Cli},
+    format::EVAL_SCALE,
+    pgn::STARTPOS,
+    sample::{self, json_string},
+};
+use value::{
+    config::{self, OutputBuckets},
+    infer::QuantisedNet,
+};
+
+fn main() {
+    let cli = Cli::new("value-infer", "Evaluates positions on the CPU with a quantised value network.")
+        .required("net", "quantised.bin exported by the value trainer")
+        .flag("json", "Write one JSON line per position");
+
+    let args = sample::options(cli).parse();
+
+    let net_path = args.get("net");
+    let json = args.flag("json");
+
+    let inputs = config::inputs();
+    let output_buckets = OutputBuckets::default();
+    let net = QuantisedNet::from_file(net_path, &config::spec(), inputs.num_inputs(), OutputBuckets::BUCKETS)
+        .unwrap_or_else(|err| cli::fail(format!("could not load '{net_path}': {err}")));
+
+    if !json {
+        println!("Loaded {net_path} with l0 = {}", net.spec().l0);
+    }
+
+    for sample in sample::load::<MontyValueFormat>(&args, STARTPOS) {
+        let eval = net.eval_fen(&inputs, &output_buckets, &sample.fen);
+
+        if json {
+            let mut line = format!(
+                "{{\"fen\":{},\"win\":{:.6},\"draw\":{:.6},\"loss\":{:.6},\"cp\":{:.0}",
+                json_string(&sample.fen),
+                eval.win,
+                eval.draw,
+                eval.loss,
+                eval.centipawns(EVAL_SCALE)
+            );
+
+            if let Some(stored) = sample.stored_json::<MontyValueFormat>() {
+                line.push_str(&format!(",\"stored\":{stored}"));
+            }
+
+            line.push('}');
+            println!("{line}");
+        } else {
+            println!("FEN   : {}", sample.fen);
+            println!("Logits: {:?}", eval.logits);
+            println!("WDL   : {:.3} / {:.3} / {:.3}", eval.win, eval.draw, eval.loss);
+            println!("EVAL  : {:.0}", eval.centipawns(EVAL_SCALE));
+        }
+    }
+}
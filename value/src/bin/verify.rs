@@ -0,0 +1,72 @@
+use std::io::{BufRead, Read};
+
+use bullet::default::formats::montyformat::MontyValueFormat;
+use montytrain_common::{
+    cli::{self, Cli},
+    progress::Progress,
+    verify::{self, Report},
+};
+use value::boards::{self, RECORD_SIZE};
+
+fn main() {
+    let args = Cli::new("verify", "Fully decodes and checks a MontyValueFormat binpack or bulletformat file.")
+        .required("input", "File to verify")
+        .flag("bulletformat", "Input is bulletformat positions rather than MontyValueFormat games")
+        .parse();
+
+    let path = args.get("input");
+    let reader = cli::open_input(path);
+
+    if args.flag("bulletformat") {
+        println!("Verifying {path:#?} as bulletformat");
+        verify_boards(reader).finish("Positions");
+    } else {
+        println!("Verifying {path:#?} as MontyValueFormat");
+        verify::verify_games::<MontyValueFormat>(reader).finish("Games");
+    }
+}
+
+fn verify_boards(mut reader: impl BufRead) -> Report {
+    let mut report = Report::default();
+    let mut progress = Progress::counter("Verified", "Positions", 1024 * 1024 * 16);
+    let mut bytes = [0; RECORD_SIZE];
+
+    loop {
+        let offset = report.bytes;
+
+        let mut read = 0;
+        while read < RECORD_SIZE {
+            match reader.read(&mut bytes[read..]) {
+                Ok(0) => break,
+                Ok(n) => read += n,
+                Err(err) => {
+                    report.problem(offset, "read error", || err.to_string());
+                    return report;
+                }
+            }
+        }
+
+        report.bytes += read as u64;
+
+        if read == 0 {
+            break;
+        }
+
+        if read < RECORD_SIZE {
+            report.problem(offset, "truncated record", || format!("{read} trailing bytes"));
+            break;
+        }
+
+        report.records += 1;
+
+        if let Err(detail) = boards::decode(&bytes) {
+            let record = report.records - 1;
+            report.problem(offset, detail, || format!("position {record}"));
+        }
+
+        progress.update(report.records);
+    }
+
+    progress.finish();
+    report
+}
@@ -0,0 +1,150 @@
+use std::io::{self, Read};
+
+use bullet::default::formats::bulletformat::ChessBoard;
+
+/// Bytes in a bulletformat record: occupancy, one nibble per piece, score,
+/// result, both king squares and three spare bytes.
+pub const RECORD_SIZE: usize = std::mem::size_of::<ChessBoard>();
+
+/// Builds a board from the fields of a record, checking each on the way
+/// rather than trusting the bytes to be a valid `ChessBoard`.
+pub fn decode(bytes: &[u8; RECORD_SIZE]) -> Result<ChessBoard, &'static str> {
+    let occ = u64::from_le_bytes(bytes[..8].try_into().unwrap());
+    let pcs = &bytes[8..24];
+    let score = i16::from_le_bytes([bytes[24], bytes[25]]);
+    let result = bytes[26];
+
+    if result > 2 {
+        return Err("invalid result");
+    }
+
+    if score == i16::MIN {
+        return Err("invalid score");
+    }
+
+    if occ.count_ones() > 32 {
+        return Err("invalid occupancy");
+    }
+
+    let mut bbs = [0; 8];
+    let mut squares = occ;
+
+    for i in 0..occ.count_ones() as usize {
+        let square = squares.trailing_zeros();
+        let bit = 1 << square;
+        squares &= squares - 1;
+
+        let piece = (pcs[i / 2] >> (4 * (i & 1))) & 15;
+        let (colour, kind) = (usize::from(piece >> 3), usize::from(piece & 7));
+
+        if kind > 5 {
+            return Err("invalid piece");
+        }
+
+        if kind == 0 && !(8..56).contains(&square) {
+            return Err("pawn on the first or last rank");
+        }
+
+        bbs[colour] |= bit;
+        bbs[2 + kind] |= bit;
+    }
+
+    if (bbs[0] & bbs[7]).count_ones() != 1 || (bbs[1] & bbs[7]).count_ones() != 1 {
+        return Err("wrong number of kings");
+    }
+
+    // records are already from the side to move's perspective
+    ChessBoard::from_raw(bbs, 0, score, f32::from(result) / 2.0).map_err(|_| "invalid position")
+}
+
+/// Reads the next record, returning `None` at a clean end of file and an
+/// error on a partial or invalid one.
+pub fn read_board(reader: &mut impl Read) -> io::Result<Option<ChessBoard>> {
+    let mut bytes = [0; RECORD_SIZE];
+    let mut read = 0;
+
+    while read < RECORD_SIZE {
+        match reader.read(&mut bytes[read..]) {
+            Ok(0) if read == 0 => return Ok(None),
+            Ok(0) => {
+                let msg = format!("truncated record, {read} trailing bytes");
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, msg));
+            }
+            Ok(n) => read += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+
+    decode(&bytes).map(Some).map_err(|msg| io::Error::new(io::ErrorKind::InvalidData, msg))
+}
+
+/// Reads up to `limit` boards from a bulletformat file.
+pub fn read_boards(path: &str, limit: usize) -> io::Result<Vec<ChessBoard>> {
+    let mut reader = io::BufReader::new(std::fs::File::open(path)?);
+    let mut boards = Vec::new();
+
+    while boards.len() < limit {
+        let at = boards.len();
+        let with_index = |err: io::Error| io::Error::new(err.kind(), format!("{path}: position {at}: {err}"));
+
+        match read_board(&mut reader).map_err(with_index)? {
+            Some(board) => boards.push(board),
+            None => break,
+        }
+    }
+
+    Ok(boards)
+}
+
+#[cfg(test)]
+mod tests {
+    use bullet::default::formats::bulletformat::BulletFormat;
+
+    use super::*;
+    use crate::infer::board_from_fen;
+
+    fn encode(boards: &[ChessBoard]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        ChessBoard::write_to_bin(&mut bytes, boards).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn decodes_what_bulletformat_writes() {
+        let boards = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        ]
+        .map(board_from_fen);
+
+        let bytes = encode(&boards);
+        let mut reader = &bytes[..];
+
+        for board in &boards {
+            let read = read_board(&mut reader).unwrap().unwrap();
+            assert!(read.into_iter().eq(board.into_iter()));
+            assert_eq!(read.score(), board.score());
+            assert_eq!(read.result(), board.result());
+        }
+
+        assert!(read_board(&mut reader).unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_partial_and_invalid_records() {
+        let bytes = encode(&[board_from_fen("8/8/8/8/8/8/8/K6k w - - 0 1")]);
+
+        let err = read_board(&mut &bytes[..RECORD_SIZE - 1]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut bad_result = bytes.clone();
+        bad_result[26] = 3;
+        assert_eq!(read_board(&mut &bad_result[..]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        let mut no_kings: [u8; RECORD_SIZE] = bytes.try_into().unwrap();
+        no_kings[8] = 0x44;
+        assert_eq!(decode(&no_kings).unwrap_err(), "wrong number of kings");
+    }
+}
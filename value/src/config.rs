@@ -0,0 +1,20 @@
+use crate::{
+    arch::ArchSpec,
+    input::{self, ThreatInputs},
+    output::MaterialBuckets,
+};
+
+/// The network being trained, shared by the trainer and the tools that
+/// consume its exports.
+pub const HIDDEN_SIZE: usize = 3072;
+pub const KING_BUCKETS: [usize; 64] = input::NO_BUCKETS;
+
+pub type OutputBuckets = MaterialBuckets<1>;
+
+pub fn inputs() -> ThreatInputs {
+    ThreatInputs::new(KING_BUCKETS)
+}
+
+pub fn spec() -> ArchSpec {
+    ArchSpec::new(HIDDEN_SIZE)
+}
@@ -0,0 +1,476 @@
+use std::path::Path;
+
+use bullet::{
+    default::formats::{
+        bulletformat::ChessBoard,
+        montyformat::chess::{Castling, Position},
+    },
+    nn::Activation,
+    trainer::{
+        default::{inputs::SparseInputType, outputs::OutputBuckets},
+        save::QuantTarget,
+    },
+};
+
+use crate::arch::{ArchSpec, OUTPUTS};
+
+/// Output of the value network, from the side to move's perspective.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Eval {
+    pub logits: [f32; OUTPUTS],
+    pub loss: f32,
+    pub draw: f32,
+    pub win: f32,
+}
+
+impl Eval {
+    pub fn score(&self) -> f32 {
+        self.win + self.draw / 2.0
+    }
+
+    /// Expected score converted back to centipawns at the trainer's `eval_scale`.
+    pub fn centipawns(&self, eval_scale: f32) -> f32 {
+        let score = self.score().clamp(0.0001, 0.9999);
+        -eval_scale * (1.0 / score - 1.0).ln()
+    }
+}
+
+/// A network loaded from the trainer's `quantised.bin`, as laid out by
+/// [`ArchSpec::saved_formats`]. `l0` is kept quantised and accumulated in
+/// integers the same way an engine would, everything after it is dequantised.
+pub struct QuantisedNet {
+    spec: ArchSpec,
+    num_inputs: usize,
+    buckets: usize,
+    pst: Option<Vec<f32>>,
+    l0w: Vec<i16>,
+    l0b: Vec<i16>,
+    l0_scale: i32,
+    layers: Vec<(Vec<f32>, Vec<f32>)>,
+    activations: Vec<fn(f32) -> f32>,
+}
+
+impl QuantisedNet {
+    /// `spec.l0` is ignored and inferred from the file size instead, so that
+    /// pruned networks can be loaded too. Accepts the file itself or the
+    /// checkpoint folder containing it.
+    pub fn from_file(path: &str, spec: &ArchSpec, num_inputs: usize, buckets: usize) -> std::io::Result<Self> {
+        let path = Path::new(path);
+        let path = if path.is_dir() { path.join("quantised.bin") } else { path.to_path_buf() };
+
+        let bytes = std::fs::read(&path)?;
+        Self::from_bytes(&bytes, spec, num_inputs, buckets)
+            .map_err(|err| std::io::Error::new(err.kind(), format!("{}: {err}", path.display())))
+    }
+
+    /// Fails if the size does not match `spec`, or if `spec` uses something
+    /// CPU inference does not support.
+    pub fn from_bytes(bytes: &[u8], spec: &ArchSpec, num_inputs: usize, buckets: usize) -> std::io::Result<Self> {
+        let invalid = |msg: String| std::io::Error::new(std::io::ErrorKind::InvalidData, msg);
+
+        let QuantTarget::I16(l0_scale) = spec.l0_quant else {
+            return Err(invalid("l0 must be quantised to i16".to_string()));
+        };
+
+        let activations = spec
+            .hidden
+            .iter()
+            .enumerate()
+            .map(|(i, layer)| {
+                activation(layer.activation)
+                    .ok_or_else(|| invalid(format!("activation of hidden layer {} is not supported", i + 1)))
+            })
+            .collect::<std::io::Result<Vec<_>>>()?;
+
+        let l0 = infer_l0(bytes.len(), spec, num_inputs, buckets)
+            .ok_or_else(|| invalid(format!("size of {} bytes does not match the architecture", bytes.len())))?;
+        let spec = ArchSpec { l0, ..spec.clone() };
+
+        let mut reader = ByteReader { bytes, offset: 0 };
+
+        let pst = spec.pst.then(|| reader.floats(QuantTarget::Float, OUTPUTS * num_inputs));
+        let l0w = reader.i16s(l0 * num_inputs);
+        let l0b = reader.i16s(l0);
+
+        let mut layers = Vec::new();
+        for (i, &(ins, outs)) in spec.layer_sizes(num_inputs).iter().enumerate().skip(1) {
+            let quant = spec.layer_quant(i);
+            let weights = reader.floats(quant, ins * outs * buckets);
+            let biases = reader.floats(quant, outs * buckets);
+            layers.push((weights, biases));
+        }
+
+        Ok(Self { spec, num_inputs, buckets, pst, l0w, l0b, l0_scale: i32::from(l0_scale), layers, activations })
+    }
+
+    pub fn spec(&self) -> &ArchSpec {
+        &self.spec
+    }
+
+    pub fn num_inputs(&self) -> usize {
+        self.num_inputs
+    }
+
+    pub fn l0_scale(&self) -> i32 {
+        self.l0_scale
+    }
+
+    pub fn l0w(&self) -> &[i16] {
+        &self.l0w
+    }
+
+    pub fn l0b(&self) -> &[i16] {
+        &self.l0b
+    }
+
+    /// Dequantised tensors in the order they are saved.
+    pub fn tensors(&self) -> Vec<(String, Vec<f32>)> {
+        let scale = self.l0_scale as f32;
+        let mut tensors = Vec::new();
+
+        if let Some(pst) = &self.pst {
+            tensors.push(("pst".to_string(), pst.clone()));
+        }
+
+        tensors.push(("l0w".to_string(), self.l0w.iter().map(|&w| f32::from(w) / scale).collect()));
+        tensors.push(("l0b".to_string(), self.l0b.iter().map(|&b| f32::from(b) / scale).collect()));
+
+        for (i, (weights, biases)) in self.layers.iter().enumerate() {
+            tensors.push((format!("l{}w", i + 1), weights.clone()));
+            tensors.push((format!("l{}b", i + 1), biases.clone()));
+        }
+
+        tensors
+    }
+
+    /// Keeps only the given pairs `(j, j + l0 / 2)` of `l0` neurons, in order.
+    pub fn pruned(&self, pairs: &[usize]) -> Self {
+        let (l0, half) = (self.spec.l0, self.spec.l0 / 2);
+        let neurons = pairs.iter().copied().chain(pairs.iter().map(|&j| j + half)).collect::<Vec<_>>();
+
+        let l0w = (0..self.num_inputs).flat_map(|feat| neurons.iter().map(move |&i| self.l0w[feat * l0 + i])).collect();
+        let l0b = neurons.iter().map(|&i| self.l0b[i]).collect();
+
+        // `l1` is transposed, so each output's weights over the pairs are contiguous
+        let mut layers = self.layers.clone();
+        let l1w = &self.layers[0].0;
+        layers[0].0 = (0..l1w.len() / half).flat_map(|o| pairs.iter().map(move |&j| l1w[o * half + j])).collect();
+
+        Self {
+            spec: ArchSpec { l0: neurons.len(), ..self.spec.clone() },
+            num_inputs: self.num_inputs,
+            buckets: self.buckets,
+            pst: self.pst.clone(),
+            l0w,
+            l0b,
+            l0_scale: self.l0_scale,
+            layers,
+            activations: self.activations.clone(),
+        }
+    }
+
+    /// Writes the network back in the layout it was loaded from, padded to a
+    /// multiple of 64 bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        if let Some(pst) = &self.pst {
+            write_floats(&mut bytes, QuantTarget::Float, pst);
+        }
+
+        for &x in self.l0w.iter().chain(&self.l0b) {
+            bytes.extend_from_slice(&x.to_le_bytes());
+        }
+
+        for (i, (weights, biases)) in self.layers.iter().enumerate() {
+            let quant = self.spec.layer_quant(i + 1);
+            write_floats(&mut bytes, quant, weights);
+            write_floats(&mut bytes, quant, biases);
+        }
+
+        bytes.resize(bytes.len().next_multiple_of(64), 0);
+        bytes
+    }
+
+    /// `l0` after CReLU, as quantised integers in `[0, l0_scale]`.
+    pub fn l0_activations(&self, features: &[usize]) -> Vec<i32> {
+        let l0 = self.spec.l0;
+        let mut acc = self.l0b.iter().map(|&b| i32::from(b)).collect::<Vec<_>>();
+
+        for &feat in features {
+            let col = &self.l0w[feat * l0..(feat + 1) * l0];
+            for (a, &w) in acc.iter_mut().zip(col) {
+                *a += i32::from(w);
+            }
+        }
+
+        for a in &mut acc {
+            *a = (*a).clamp(0, self.l0_scale);
+        }
+
+        acc
+    }
+
+    pub fn eval_features(&self, features: &[usize], bucket: usize) -> Eval {
+        assert!(bucket < self.buckets, "Invalid output bucket {bucket}!");
+
+        let acc = self.l0_activations(features);
+        let half = self.spec.l0 / 2;
+        let scale = self.l0_scale as f32;
+
+        let mut out = (0..half).map(|i| (acc[i] * acc[i + half]) as f32 / (scale * scale)).collect::<Vec<_>>();
+
+        let sizes = self.spec.layer_sizes(self.num_inputs);
+        for (i, (weights, biases)) in self.layers.iter().enumerate() {
+            let (ins, outs) = sizes[i + 1];
+            let total = outs * self.buckets;
+
+            let mut next = biases[bucket * outs..(bucket + 1) * outs].to_vec();
+
+            for (o, n) in next.iter_mut().enumerate() {
+                let o = bucket * outs + o;
+                for (j, &x) in out.iter().enumerate() {
+                    // l1 is stored transposed, see `ArchSpec::saved_formats`
+                    let w = if i == 0 { weights[o * ins + j] } else { weights[j * total + o] };
+                    *n += w * x;
+                }
+            }
+
+            if let Some(activate) = self.activations.get(i) {
+                for n in &mut next {
+                    *n = activate(*n);
+                }
+            }
+
+            out = next;
+        }
+
+        let mut logits = [0.0; OUTPUTS];
+        logits.copy_from_slice(&out);
+
+        if let Some(pst) = &self.pst {
+            for &feat in features {
+                for (k, l) in logits.iter_mut().enumerate() {
+                    *l += pst[feat * OUTPUTS + k];
+                }
+            }
+        }
+
+        // targets are ordered by result index, i.e. loss, draw, win
+        let max = logits.iter().fold(f32::NEG_INFINITY, |a, &b| a.max(b));
+        let exp = logits.map(|l| (l - max).exp());
+        let sum = exp.iter().sum::<f32>();
+
+        Eval { logits, loss: exp[0] / sum, draw: exp[1] / sum, win: exp[2] / sum }
+    }
+
+    pub fn eval_board<T, O>(&self, inputs: &T, output_buckets: &O, board: &ChessBoard) -> Eval
+    where
+        T: SparseInputType<RequiredDataType = ChessBoard>,
+        O: OutputBuckets<ChessBoard>,
+    {
+        self.eval_features(&features(inputs, board), usize::from(output_buckets.bucket(board)))
+    }
+
+    /// Mean cross-entropy of the WDL against the game result, and how often
+    /// the most likely outcome is the result.
+    pub fn validate<T, O>(&self, inputs: &T, output_buckets: &O, boards: &[ChessBoard]) -> (f32, f32)
+    where
+        T: SparseInputType<RequiredDataType = ChessBoard>,
+        O: OutputBuckets<ChessBoard>,
+    {
+        let mut loss = 0.0;
+        let mut correct = 0;
+
+        for board in boards {
+            let eval = self.eval_board(inputs, output_buckets, board);
+            let probs = [eval.loss, eval.draw, eval.win];
+            let target = (2.0 * board.result()).round() as usize;
+
+            loss -= probs[target].max(1e-9).ln();
+            correct += usize::from(probs.iter().all(|&p| p <= probs[target]));
+        }
+
+        let n = boards.len().max(1) as f32;
+        (loss / n, correct as f32 / n)
+    }
+
+    pub fn eval_fen<T, O>(&self, inputs: &T, output_buckets: &O, fen: &str) -> Eval
+    where
+        T: SparseInputType<RequiredDataType = ChessBoard>,
+        O: OutputBuckets<ChessBoard>,
+    {
+        self.eval_board(inputs, output_buckets, &board_from_fen(fen))
+    }
+}
+
+/// Side-to-move features of a board.
+pub fn features<T: SparseInputType<RequiredDataType = ChessBoard>>(inputs: &T, board: &ChessBoard) -> Vec<usize> {
+    let mut features = Vec::new();
+    inputs.map_features(board, |stm, _| features.push(stm));
+    features
+}
+
+pub fn board_from_fen(fen: &str) -> ChessBoard {
+    let mut castling = Castling::default();
+    let pos = Position::parse_fen(fen, &mut castling);
+    ChessBoard::from_raw(pos.bbs(), pos.stm(), 0, 0.5).unwrap()
+}
+
+/// `None` if CPU inference does not support the activation.
+fn activation(activation: Activation) -> Option<fn(f32) -> f32> {
+    let activate: fn(f32) -> f32 = match activation {
+        Activation::Identity => |x| x,
+        Activation::ReLU => |x| x.max(0.0),
+        Activation::CReLU => |x| x.clamp(0.0, 1.0),
+        Activation::SCReLU => |x| x.clamp(0.0, 1.0).powi(2),
+        Activation::SqrReLU => |x| x.max(0.0).powi(2),
+        _ => return None,
+    };
+
+    Some(activate)
+}
+
+fn quant_bytes(quant: QuantTarget) -> usize {
+    match quant {
+        QuantTarget::Float | QuantTarget::I32(_) => 4,
+        QuantTarget::I16(_) => 2,
+        QuantTarget::I8(_) => 1,
+    }
+}
+
+/// The saved file is linear in `l0` (per pair of neurons), up to at most
+/// 64 bytes of trailing padding.
+fn infer_l0(len: usize, spec: &ArchSpec, num_inputs: usize, buckets: usize) -> Option<usize> {
+    let size = |l0: usize| {
+        let spec = ArchSpec { l0, ..spec.clone() };
+        let mut total = if spec.pst { 4 * OUTPUTS * num_inputs } else { 0 };
+
+        for (i, &(ins, outs)) in spec.layer_sizes(num_inputs).iter().enumerate() {
+            let outs = if i == 0 { outs } else { outs * buckets };
+            total += quant_bytes(spec.layer_quant(i)) * (ins + 1) * outs;
+        }
+
+        total
+    };
+
+    let fixed = size(0);
+    let per_pair = size(2) - fixed;
+    let l0 = 2 * len.checked_sub(fixed)? / per_pair;
+
+    (l0 > 0 && len - size(l0) < 64).then_some(l0)
+}
+
+fn write_floats(bytes: &mut Vec<u8>, quant: QuantTarget, vals: &[f32]) {
+    for &x in vals {
+        match quant {
+            QuantTarget::Float => bytes.extend_from_slice(&x.to_le_bytes()),
+            QuantTarget::I32(q) => bytes.extend_from_slice(&((x * q as f32).round() as i32).to_le_bytes()),
+            QuantTarget::I16(q) => bytes.extend_from_slice(&((x * q as f32).round() as i16).to_le_bytes()),
+            QuantTarget::I8(q) => bytes.extend_from_slice(&((x * q as f32).round() as i8).to_le_bytes()),
+        }
+    }
+}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl ByteReader<'_> {
+    fn take<const N: usize>(&mut self) -> [u8; N] {
+        let mut buf = [0; N];
+        buf.copy_from_slice(&self.bytes[self.offset..self.offset + N]);
+        self.offset += N;
+        buf
+    }
+
+    fn i16s(&mut self, count: usize) -> Vec<i16> {
+        (0..count).map(|_| i16::from_le_bytes(self.take())).collect()
+    }
+
+    fn floats(&mut self, quant: QuantTarget, count: usize) -> Vec<f32> {
+        (0..count)
+            .map(|_| match quant {
+                QuantTarget::Float => f32::from_le_bytes(self.take()),
+                QuantTarget::I32(q) => i32::from_le_bytes(self.take()) as f32 / q as f32,
+                QuantTarget::I16(q) => f32::from(i16::from_le_bytes(self.take())) / q as f32,
+                QuantTarget::I8(q) => f32::from(i8::from_le_bytes(self.take())) / q as f32,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arch::Layer;
+
+    const NUM_INPUTS: usize = 2;
+
+    /// `2 -> 2 (pairwise) -> 2 (ReLU) -> 3 + pst` with a single output bucket.
+    fn spec() -> ArchSpec {
+        ArchSpec {
+            l0: 2,
+            l0_quant: QuantTarget::I16(64),
+            hidden: vec![Layer { size: 2, activation: Activation::ReLU, quant: QuantTarget::Float }],
+            output_quant: QuantTarget::Float,
+            pst: true,
+        }
+    }
+
+    fn bytes() -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        // pst
+        write_floats(&mut bytes, QuantTarget::Float, &[0.1, 0.0, -0.1, 0.0, 0.2, 0.0]);
+
+        // l0w (per feature) and l0b, already quantised
+        for x in [32i16, 64, 16, -32, 0, 16] {
+            bytes.extend_from_slice(&x.to_le_bytes());
+        }
+
+        // l1w (transposed) and l1b
+        write_floats(&mut bytes, QuantTarget::Float, &[2.0, -1.0, 0.5, 0.25]);
+
+        // l2w and l2b
+        write_floats(&mut bytes, QuantTarget::Float, &[1.0, 0.0, -1.0, 0.5, 0.5, 0.5, 0.0, 0.1, 0.2]);
+
+        bytes
+    }
+
+    fn assert_eval(eval: Eval, logits: [f32; OUTPUTS], wdl: [f32; OUTPUTS]) {
+        for (a, b) in eval.logits.iter().zip(logits).chain([eval.loss, eval.draw, eval.win].iter().zip(wdl)) {
+            assert!((a - b).abs() < 1e-5, "{:?} != {logits:?} / {wdl:?}", eval);
+        }
+    }
+
+    #[test]
+    fn reference_outputs() {
+        let net = QuantisedNet::from_bytes(&bytes(), &spec(), NUM_INPUTS, 1).unwrap();
+        assert_eq!(net.spec().l0, 2);
+
+        // l0 = [48, 48] / 64, l1 = [1.625, 0]
+        assert_eval(net.eval_features(&[0, 1], 0), [1.725, 0.3, -1.525], [0.781688, 0.188003, 0.030309]);
+
+        // l0 = [16, 0] / 64, l1 = [0.5, 0.25]
+        assert_eval(net.eval_features(&[1], 0), [0.625, 0.425, -0.175], [0.440905, 0.360983, 0.198112]);
+    }
+
+    #[test]
+    fn rejects_unsupported_nets() {
+        let unsupported = [
+            ArchSpec { l0_quant: QuantTarget::Float, ..spec() },
+            ArchSpec {
+                hidden: vec![Layer { size: 2, activation: Activation::Sigmoid, quant: QuantTarget::Float }],
+                ..spec()
+            },
+        ];
+
+        for spec in unsupported {
+            assert!(QuantisedNet::from_bytes(&bytes(), &spec, NUM_INPUTS, 1).is_err());
+        }
+
+        assert!(QuantisedNet::from_bytes(&bytes()[..40], &spec(), NUM_INPUTS, 1).is_err());
+    }
+}
@@ -11,7 +11,35 @@ use bullet::default::{
 use crate::{consts::offsets, threats::map_piece_threat};
 
 const TOTAL_THREATS: usize = 2 * offsets::END;
-const TOTAL: usize = TOTAL_THREATS + 768;
+
+/// A single bucket, equivalent to plain horizontally-mirrored piece-square features.
+pub const NO_BUCKETS: [usize; 64] = [0; 64];
+
+/// Layouts are indexed by the (mirrored) square of the side-to-move's king,
+/// so only files A-D are ever looked up.
+#[rustfmt::skip]
+pub const KING_BUCKETS_4: [usize; 64] = [
+    0, 0, 1, 1, 1, 1, 0, 0,
+    2, 2, 2, 2, 2, 2, 2, 2,
+    3, 3, 3, 3, 3, 3, 3, 3,
+    3, 3, 3, 3, 3, 3, 3, 3,
+    3, 3, 3, 3, 3, 3, 3, 3,
+    3, 3, 3, 3, 3, 3, 3, 3,
+    3, 3, 3, 3, 3, 3, 3, 3,
+    3, 3, 3, 3, 3, 3, 3, 3,
+];
+
+#[rustfmt::skip]
+pub const KING_BUCKETS_8: [usize; 64] = [
+    0, 1, 2, 3, 3, 2, 1, 0,
+    4, 4, 5, 5, 5, 5, 4, 4,
+    6, 6, 6, 6, 6, 6, 6, 6,
+    6, 6, 6, 6, 6, 6, 6, 6,
+    7, 7, 7, 7, 7, 7, 7, 7,
+    7, 7, 7, 7, 7, 7, 7, 7,
+    7, 7, 7, 7, 7, 7, 7, 7,
+    7, 7, 7, 7, 7, 7, 7, 7,
+];
 
 static COUNT: AtomicUsize = AtomicUsize::new(0);
 static SQRED: AtomicUsize = AtomicUsize::new(0);
@@ -34,15 +62,58 @@ pub fn print_feature_stats() {
     println!("Active Features: {mean:.3} +- {pct:.3} (95%)");
 }
 
-fn map_features<F: FnMut(usize)>(mut bbs: [u64; 8], mut f: F) {
+fn map_features<F: FnMut(usize)>(bbs: [u64; 8], buckets: &[usize; 64], mut f: F) {
+    let mut count = 0;
+
+    map_features_where(
+        bbs,
+        buckets,
+        |_, _| true,
+        |feat| {
+            f(feat);
+            count += 1;
+        },
+    );
+
+    if TRACK {
+        COUNT.fetch_add(count, Ordering::Relaxed);
+        SQRED.fetch_add(count * count, Ordering::Relaxed);
+        let evals = EVALS.fetch_add(1, Ordering::Relaxed);
+        MAX.fetch_max(count, Ordering::Relaxed);
+
+        if (evals + 1) % (16384 * 6104) == 0 {
+            print_feature_stats();
+        }
+    }
+}
+
+/// Returns whether the board gets horizontally mirrored, and the king bucket
+/// of the mirrored board.
+pub(crate) fn mirror_and_bucket(bbs: &[u64; 8], buckets: &[usize; 64]) -> (bool, usize) {
+    let ksq = (bbs[0] & bbs[Piece::KING]).trailing_zeros() as usize;
+    let mirror = ksq % 8 > 3;
+    (mirror, buckets[if mirror { ksq ^ 7 } else { ksq }])
+}
+
+/// Maps only the features for which `keep(attacker, target)` holds, where the
+/// squares are given in the horizontally mirrored frame. Piece-square features
+/// are passed as `keep(sq, sq)`.
+pub(crate) fn map_features_where<K, F>(mut bbs: [u64; 8], buckets: &[usize; 64], keep: K, mut f: F)
+where
+    K: Fn(usize, usize) -> bool,
+    F: FnMut(usize),
+{
+    let (mirror, bucket) = mirror_and_bucket(&bbs, buckets);
+
     // horiontal mirror
-    let ksq = (bbs[0] & bbs[Piece::KING]).trailing_zeros();
-    if ksq % 8 > 3 {
+    if mirror {
         for bb in bbs.iter_mut() {
             *bb = flip_horizontal(*bb);
         }
     };
 
+    let psq_offset = TOTAL_THREATS + 768 * bucket;
+
     let mut pieces = [13; 64];
     for side in [Side::WHITE, Side::BLACK] {
         for piece in Piece::PAWN..=Piece::KING {
@@ -51,8 +122,6 @@ fn map_features<F: FnMut(usize)>(mut bbs: [u64; 8], mut f: F) {
         }
     }
 
-    let mut count = 0;
-
     let occ = bbs[0] | bbs[1];
 
     for side in [Side::WHITE, Side::BLACK] {
@@ -71,32 +140,26 @@ fn map_features<F: FnMut(usize)>(mut bbs: [u64; 8], mut f: F) {
                     _ => unreachable!(),
                 } & occ;
 
-                f(TOTAL_THREATS + [0, 384][side] + 64 * (piece - 2) + sq);
-                count += 1;
+                if keep(sq, sq) {
+                    f(psq_offset + [0, 384][side] + 64 * (piece - 2) + sq);
+                }
+
                 map_bb(threats, |dest| {
+                    if !keep(sq, dest) {
+                        return;
+                    }
+
                     let enemy = (1 << dest) & opps > 0;
                     if let Some(idx) = map_piece_threat(piece, sq, dest, pieces[dest], enemy) {
                         f(side_offset + idx);
-                        count += 1;
                     }
                 });
             });
         }
     }
-
-    if TRACK {
-        COUNT.fetch_add(count, Ordering::Relaxed);
-        SQRED.fetch_add(count * count, Ordering::Relaxed);
-        let evals = EVALS.fetch_add(1, Ordering::Relaxed);
-        MAX.fetch_max(count, Ordering::Relaxed);
-
-        if (evals + 1) % (16384 * 6104) == 0 {
-            print_feature_stats();
-        }
-    }
 }
 
-fn map_bb<F: FnMut(usize)>(mut bb: u64, mut f: F) {
+pub(crate) fn map_bb<F: FnMut(usize)>(mut bb: u64, mut f: F) {
     while bb > 0 {
         let sq = bb.trailing_zeros() as usize;
         f(sq);
@@ -104,7 +167,7 @@ fn map_bb<F: FnMut(usize)>(mut bb: u64, mut f: F) {
     }
 }
 
-fn flip_horizontal(mut bb: u64) -> u64 {
+pub(crate) fn flip_horizontal(mut bb: u64) -> u64 {
     const K1: u64 = 0x5555555555555555;
     const K2: u64 = 0x3333333333333333;
     const K4: u64 = 0x0f0f0f0f0f0f0f0f;
@@ -113,13 +176,43 @@ fn flip_horizontal(mut bb: u64) -> u64 {
     ((bb >> 4) & K4) | ((bb & K4) << 4)
 }
 
-#[derive(Clone, Copy, Default)]
-pub struct ThreatInputs;
+#[derive(Clone, Copy)]
+pub struct ThreatInputs {
+    buckets: [usize; 64],
+    num_buckets: usize,
+}
+
+impl Default for ThreatInputs {
+    fn default() -> Self {
+        Self::new(NO_BUCKETS)
+    }
+}
+
+impl ThreatInputs {
+    pub fn new(buckets: [usize; 64]) -> Self {
+        Self { buckets, num_buckets: montytrain_common::buckets::count(&buckets) }
+    }
+
+    pub fn num_buckets(&self) -> usize {
+        self.num_buckets
+    }
+
+    pub fn buckets(&self) -> &[usize; 64] {
+        &self.buckets
+    }
+
+    /// Maps the features of a board given as `[ours, theirs, pawns, ..., kings]`.
+    pub fn map_bbs<F: FnMut(usize)>(&self, bbs: [u64; 8], f: F) {
+        map_features(bbs, &self.buckets, f);
+    }
+}
+
 impl inputs::SparseInputType for ThreatInputs {
     type RequiredDataType = ChessBoard;
 
+    /// Laid out as `[threats; 2 * offsets::END]` followed by `[piece-square; 768]` per king bucket.
     fn num_inputs(&self) -> usize {
-        TOTAL
+        TOTAL_THREATS + 768 * self.num_buckets
     }
 
     fn max_active(&self) -> usize {
@@ -136,14 +229,22 @@ impl inputs::SparseInputType for ThreatInputs {
             bbs[pt] |= bit;
         }
 
-        map_features(bbs, |stm| f(stm, stm));
+        self.map_bbs(bbs, |stm| f(stm, stm));
     }
 
     fn shorthand(&self) -> String {
-        format!("{TOTAL}")
+        if self.num_buckets == 1 {
+            format!("{}", self.num_inputs())
+        } else {
+            format!("{TOTAL_THREATS}+768x{}", self.num_buckets)
+        }
     }
 
     fn description(&self) -> String {
-        "Threat inputs".to_string()
+        if self.num_buckets == 1 {
+            "Threat inputs".to_string()
+        } else {
+            format!("Threat inputs with {} king buckets", self.num_buckets)
+        }
     }
 }
@@ -0,0 +1,311 @@
+use std::io::{self, BufRead, Read, Seek, SeekFrom, Write};
+
+use montytrain_common::progress::Progress;
+
+/// Games checked when detecting the version of a file.
+const DETECT_GAMES: usize = 16;
+/// Longer games are assumed to be misframed.
+const MAX_PLIES: usize = 4096;
+
+/// Layouts of a `MontyValueFormat` game. Both start with a 32-byte compressed
+/// board followed by stm, en passant square, castling rights and halfmove
+/// clock, and end with 4-byte move records terminated by all zeros. `Current`
+/// inserts a 2-byte fullmove counter before the rook files and result.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Version {
+    Old,
+    Current,
+}
+
+impl Version {
+    fn header_len(self) -> usize {
+        match self {
+            Version::Old => 41,
+            Version::Current => 43,
+        }
+    }
+
+    /// Rejects headers whose fields are out of range for this layout.
+    fn check_header(self, header: &[u8]) -> Result<(), &'static str> {
+        let [stm, enp, rights, _halfm] = [header[32], header[33], header[34], header[35]];
+
+        if stm > 1 {
+            return Err("side to move out of range");
+        }
+
+        if enp != 0 && !(16..24).contains(&enp) && !(40..48).contains(&enp) {
+            return Err("en passant square out of range");
+        }
+
+        if rights > 15 {
+            return Err("castling rights out of range");
+        }
+
+        let tail = match self {
+            Version::Old => &header[36..41],
+            Version::Current => &header[38..43],
+        };
+
+        if tail[..4].iter().any(|&file| file > 7) {
+            return Err("rook file out of range");
+        }
+
+        if tail[4] > 2 {
+            return Err("result out of range");
+        }
+
+        Ok(())
+    }
+}
+
+pub enum GameError {
+    /// End of file in the middle of a game, with the bytes already read.
+    Truncated(usize),
+    Invalid(&'static str),
+    Io(io::Error),
+}
+
+/// A game read in the layout of its file.
+pub struct Game {
+    header: Vec<u8>,
+    moves: Vec<[u8; 4]>,
+}
+
+impl Game {
+    fn len(&self) -> usize {
+        self.header.len() + 4 * (self.moves.len() + 1)
+    }
+
+    fn plies(&self) -> usize {
+        self.moves.len()
+    }
+
+    /// Writes the game in the current layout.
+    pub fn write_current(&self, version: Version, writer: &mut impl Write) -> io::Result<()> {
+        match version {
+            Version::Old => {
+                writer.write_all(&self.header[..36])?;
+                writer.write_all(&[0; 2])?;
+                writer.write_all(&self.header[36..])?;
+            }
+            Version::Current => writer.write_all(&self.header)?,
+        }
+
+        for mov in &self.moves {
+            writer.write_all(mov)?;
+        }
+
+        writer.write_all(&[0; 4])
+    }
+}
+
+/// Reads the next game, returning `None` at a clean end of file.
+pub fn read_game(reader: &mut impl BufRead, version: Version) -> Result<Option<Game>, GameError> {
+    match reader.fill_buf() {
+        Ok([]) => return Ok(None),
+        Ok(_) => {}
+        Err(err) => return Err(GameError::Io(err)),
+    }
+
+    let mut header = vec![0; version.header_len()];
+    read_fully(reader, &mut header, 0)?;
+    version.check_header(&header).map_err(GameError::Invalid)?;
+
+    let mut moves = Vec::new();
+
+    loop {
+        let mut mov = [0; 4];
+        read_fully(reader, &mut mov, header.len() + 4 * moves.len())?;
+
+        if mov == [0; 4] {
+            return Ok(Some(Game { header, moves }));
+        }
+
+        // a1a1 is never a move, so this is a misframed terminator
+        if mov[..2] == [0; 2] {
+            return Err(GameError::Invalid("null move"));
+        }
+
+        if moves.len() == MAX_PLIES {
+            return Err(GameError::Invalid("game is not terminated"));
+        }
+
+        moves.push(mov);
+    }
+}
+
+fn read_fully(reader: &mut impl Read, buf: &mut [u8], already_read: usize) -> Result<(), GameError> {
+    let mut read = 0;
+
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => return Err(GameError::Truncated(already_read + read)),
+            Ok(n) => read += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(GameError::Io(err)),
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether the first games parse cleanly in `version`. A partial game at the
+/// end of the file does not count against it.
+fn plausible(reader: &mut impl BufRead, version: Version) -> io::Result<bool> {
+    let mut games = 0;
+
+    while games < DETECT_GAMES {
+        match read_game(reader, version) {
+            Ok(Some(_)) => games += 1,
+            Ok(None) | Err(GameError::Truncated(_)) => break,
+            Err(GameError::Invalid(_)) => return Ok(false),
+            Err(GameError::Io(err)) => return Err(err),
+        }
+    }
+
+    Ok(games > 0)
+}
+
+/// Tells the layouts apart by which one the first games parse in, leaving the
+/// reader at the start.
+pub fn detect<R: BufRead + Seek>(reader: &mut R) -> io::Result<Version> {
+    let mut plausible_from_start = |version| {
+        reader.seek(SeekFrom::Start(0))?;
+        plausible(reader, version)
+    };
+
+    let found = (plausible_from_start(Version::Old)?, plausible_from_start(Version::Current)?);
+    reader.seek(SeekFrom::Start(0))?;
+
+    let invalid = |msg| Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+
+    match found {
+        (true, false) => Ok(Version::Old),
+        (false, true) => Ok(Version::Current),
+        (true, true) => invalid("could not tell which version it is, it parses as both"),
+        (false, false) => invalid("not a recognised MontyValueFormat file"),
+    }
+}
+
+/// What [`convert`] read.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Summary {
+    pub version: Version,
+    pub games: u64,
+    pub positions: u64,
+    /// Size and offset of a partial game at the end of the file.
+    pub dropped: Option<(usize, usize)>,
+}
+
+/// Validates every game of a file in the `version` found by [`detect`] and,
+/// given a writer, writes it in the current layout. Files that are already
+/// current are only checked, never converted again.
+pub fn convert(
+    reader: &mut impl BufRead,
+    version: Version,
+    mut writer: Option<&mut impl Write>,
+    progress: &mut Progress,
+) -> io::Result<Summary> {
+    if version == Version::Current && writer.is_some() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "already in the current layout"));
+    }
+
+    let mut summary = Summary { version, games: 0, positions: 0, dropped: None };
+    let mut offset = 0;
+
+    loop {
+        let game = match read_game(reader, version) {
+            Ok(Some(game)) => game,
+            Ok(None) => break,
+            Err(GameError::Truncated(bytes)) => {
+                summary.dropped = Some((bytes, offset));
+                break;
+            }
+            Err(GameError::Invalid(reason)) => {
+                let msg = format!("invalid game at byte {offset}: {reason}");
+                return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+            }
+            Err(GameError::Io(err)) => return Err(err),
+        };
+
+        if let Some(writer) = writer.as_mut() {
+            game.write_current(version, writer)?;
+        }
+
+        offset += game.len();
+        summary.games += 1;
+        summary.positions += game.plies() as u64;
+        progress.update(summary.games);
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    // Three games with 2, 0 and 3 moves, black to move in the second and an
+    // en passant square in the third. `current.bin` is `old.bin` converted,
+    // so its fullmove counters are zero.
+    const OLD: &[u8] = include_bytes!("../tests/fixtures/old.bin");
+    const CURRENT: &[u8] = include_bytes!("../tests/fixtures/current.bin");
+
+    fn run(bytes: &[u8], write: bool) -> (io::Result<Summary>, Vec<u8>) {
+        let mut output = Vec::new();
+        let mut progress = Progress::counter("Converted", "Games", u64::MAX);
+        let mut reader = Cursor::new(bytes);
+        let summary = detect(&mut reader)
+            .and_then(|version| convert(&mut reader, version, write.then_some(&mut output), &mut progress));
+        (summary, output)
+    }
+
+    #[test]
+    fn detects_both_layouts() {
+        assert_eq!(detect(&mut Cursor::new(OLD)).unwrap(), Version::Old);
+        assert_eq!(detect(&mut Cursor::new(CURRENT)).unwrap(), Version::Current);
+    }
+
+    #[test]
+    fn converts_old_to_current() {
+        let (summary, output) = run(OLD, true);
+
+        let summary = summary.unwrap();
+        assert_eq!(summary, Summary { version: Version::Old, games: 3, positions: 5, dropped: None });
+        assert_eq!(output, CURRENT);
+    }
+
+    #[test]
+    fn current_is_checked_but_not_converted() {
+        let (summary, output) = run(CURRENT, true);
+        assert_eq!(summary.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert!(output.is_empty());
+
+        let (summary, _) = run(CURRENT, false);
+        assert_eq!(summary.unwrap(), Summary { version: Version::Current, games: 3, positions: 5, dropped: None });
+    }
+
+    #[test]
+    fn drops_a_game_truncated_at_eof() {
+        let last = OLD.len() - (41 + 4 * 4);
+        let truncated = &OLD[..OLD.len() - 6];
+
+        let (summary, output) = run(truncated, true);
+
+        let summary = summary.unwrap();
+        assert_eq!(summary.games, 2);
+        assert_eq!(summary.dropped, Some((41 + 4 * 4 - 6, last)));
+        assert_eq!(output, CURRENT[..CURRENT.len() - (43 + 4 * 4)]);
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        let garbage = (0..200u32).map(|i| (i.wrapping_mul(0x9E37_79B9) >> 24) as u8).collect::<Vec<_>>();
+
+        for bytes in [&garbage[..], &[0xFF; 64], &[]] {
+            assert_eq!(detect(&mut Cursor::new(bytes)).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        }
+    }
+}
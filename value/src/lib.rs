@@ -0,0 +1,10 @@
+pub mod arch;
+pub mod boards;
+pub mod config;
+pub mod consts;
+pub mod infer;
+pub mod input;
+pub mod layout;
+pub mod output;
+pub mod threats;
+pub mod update;
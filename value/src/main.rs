@@ -1,11 +1,18 @@
-mod arch;
-mod consts;
-mod input;
-mod threats;
+use std::cell::{Cell, RefCell};
 
-use arch::make_trainer;
-use consts::indices;
-use input::ThreatInputs;
+use value::{
+    arch::make_trainer,
+    boards,
+    config::{self, OutputBuckets},
+    consts::indices,
+    infer::{self, QuantisedNet},
+};
+
+use montytrain_common::{
+    cli::Cli,
+    interrupt::{self, Progress},
+    runlog::{Entry, RunLog},
+};
 
 use bullet::{
     nn::optimiser,
@@ -14,15 +21,27 @@ use bullet::{
             formats::montyformat::chess::{Move, Position},
             inputs::SparseInputType,
             loader,
+            outputs::OutputBuckets as _,
+        },
+        schedule::{
+            lr::{self, LrScheduler},
+            wdl, TrainingSchedule, TrainingSteps,
         },
-        schedule::{lr, wdl, TrainingSchedule, TrainingSteps},
-        settings::LocalSettings,
+        settings::{LocalSettings, TestDataset},
     },
 };
 
-const HIDDEN_SIZE: usize = 3072;
+/// Validation games split off by `interleave --validation`, converted with
+/// `bulletformat`. Skipped if it does not exist.
+const TEST_SET: &str = "data/validation.data";
+/// Positions of the test set the CPU evaluates at each save for `log.csv`.
+const VALIDATION_POSITIONS: usize = 16_384;
 
 fn main() {
+    let args = Cli::new("value", "Trains the value network.")
+        .optional("resume", "Checkpoint folder to continue from, such as one written when interrupted")
+        .parse();
+
     println!("Attacks:");
     println!("Pawn   : {}", indices::PAWN);
     println!("Bishop : {}", indices::BISHOP[64]);
@@ -31,18 +50,27 @@ fn main() {
     println!("Queen  : {}", indices::QUEEN[64]);
     println!("King   : {}", indices::KING[64]);
 
-    println!("Inputs: {}", ThreatInputs.num_inputs());
-    let mut trainer = make_trainer::<ThreatInputs>(HIDDEN_SIZE);
+    let inputs = config::inputs();
+    println!("Inputs: {} ({})", inputs.num_inputs(), inputs.description());
+    let spec = config::spec();
+    let mut trainer = make_trainer(inputs, OutputBuckets::default(), &spec);
+
+    let batches_per_superbatch = 6104;
+
+    let start_superbatch = match args.get_opt("resume") {
+        Some(dir) => {
+            trainer.load_from_checkpoint(dir);
+            let start = Progress::read(dir).unwrap().next_superbatch;
+            println!("Resuming from {dir} at superbatch {start}");
+            start
+        }
+        None => 1,
+    };
 
     let schedule = TrainingSchedule {
         net_id: "4096EXP".to_string(),
         eval_scale: 400.0,
-        steps: TrainingSteps {
-            batch_size: 16_384,
-            batches_per_superbatch: 6104,
-            start_superbatch: 1,
-            end_superbatch: 3000,
-        },
+        steps: TrainingSteps { batch_size: 16_384, batches_per_superbatch, start_superbatch, end_superbatch: 3000 },
         wdl_scheduler: wdl::ConstantWDL { value: 1.0 },
         lr_scheduler: lr::ExponentialDecayLR { initial_lr: 0.001, final_lr: 0.0000001, final_superbatch: 3000 },
         save_rate: 100,
@@ -53,7 +81,12 @@ fn main() {
 
     trainer.set_optimiser_params(optimiser_params);
 
-    let settings = LocalSettings { threads: 8, test_set: None, output_directory: "checkpoints", batch_queue_size: 32 };
+    let test_set = std::path::Path::new(TEST_SET).is_file().then(|| TestDataset::at_path(TEST_SET, 32));
+    if test_set.is_none() {
+        println!("No test set at {TEST_SET:#?}");
+    }
+
+    let settings = LocalSettings { threads: 8, test_set, output_directory: "checkpoints", batch_queue_size: 32 };
 
     fn filter(_: &Position, _: Move, _: i16, _: f32) -> bool {
         true
@@ -67,7 +100,86 @@ fn main() {
         filter,
     );
 
-    trainer.run(&schedule, &settings, &data_loader);
+    let validation = if std::path::Path::new(TEST_SET).is_file() {
+        boards::read_boards(TEST_SET, VALIDATION_POSITIONS).unwrap()
+    } else {
+        Vec::new()
+    };
+
+    let log = RefCell::new(RunLog::open(settings.output_directory).unwrap());
+    let steps = schedule.steps;
+    let log_rate = 64;
+
+    // mean loss of each `log_rate` batches
+    let window_loss = Cell::new((0.0f32, 0usize));
+
+    interrupt::install();
+
+    // bullet saves before calling back at the end of a superbatch, so validation runs on the quantised net just
+    // written. An interruption stops after the current batch.
+    trainer.train_custom(
+        &schedule,
+        &settings,
+        &data_loader,
+        |trainer, superbatch, batch, error| {
+            let (total, batches) = window_loss.get();
+            window_loss.set((total + error, batches + 1));
+
+            if batch % log_rate == 0 {
+                let (total, batches) = window_loss.take();
+                let mut log = log.borrow_mut();
+                let positions_per_sec = log.speed(batches * steps.batch_size);
+
+                let entry = Entry {
+                    superbatch,
+                    batch,
+                    loss: Some(total / batches as f32),
+                    lr: schedule.lr_scheduler.lr(batch, superbatch),
+                    positions_per_sec: Some(positions_per_sec),
+                    ..Default::default()
+                };
+
+                log.write(&entry).unwrap();
+            }
+
+            if interrupt::requested() {
+                let dir = format!("{}/{}-{superbatch}-interrupted", settings.output_directory, schedule.net_id);
+                trainer.save_to_checkpoint(&dir);
+                Progress::interrupted(superbatch).write(&dir).unwrap();
+                println!("Saved {dir}, continue with --resume {dir}");
+                std::process::exit(0);
+            }
+        },
+        |superbatch, _, schedule, settings| {
+            if superbatch % schedule.save_rate != 0 && superbatch != steps.end_superbatch {
+                return;
+            }
+
+            let dir = format!("{}/{}-{superbatch}", settings.output_directory, schedule.net_id);
+            Progress::finished(superbatch).write(&dir).unwrap();
+
+            if !validation.is_empty() {
+                if let Ok(net) = QuantisedNet::from_file(&dir, &spec, inputs.num_inputs(), OutputBuckets::BUCKETS) {
+                    let (loss, accuracy) = net.validate(&inputs, &OutputBuckets::default(), &validation);
+
+                    let entry = Entry {
+                        superbatch,
+                        batch: steps.batches_per_superbatch,
+                        lr: schedule.lr_scheduler.lr(steps.batches_per_superbatch, superbatch),
+                        validation_loss: Some(loss),
+                        validation_accuracy: Some(accuracy),
+                        ..Default::default()
+                    };
+
+                    log.borrow_mut().write(&entry).unwrap();
+                }
+            }
+        },
+    );
+
+    let final_net =
+        format!("{}/{}-{}/quantised.bin", settings.output_directory, schedule.net_id, schedule.steps.end_superbatch);
+    let quantised = QuantisedNet::from_file(&final_net, &spec, inputs.num_inputs(), OutputBuckets::BUCKETS).ok();
 
     for fen in [
         "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
@@ -79,5 +191,16 @@ fn main() {
         let eval = trainer.eval(fen);
         println!("FEN: {fen}");
         println!("EVAL: {}", 400.0 * eval);
+
+        if let Some(net) = &quantised {
+            let eval = net.eval_fen(&inputs, &OutputBuckets::default(), fen);
+            println!(
+                "QUANTISED: {:.0} (W {:.3} D {:.3} L {:.3})",
+                eval.centipawns(400.0),
+                eval.win,
+                eval.draw,
+                eval.loss
+            );
+        }
     }
 }
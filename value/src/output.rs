@@ -0,0 +1,49 @@
+use bullet::{
+    default::formats::{bulletformat::ChessBoard, montyformat::chess::Piece},
+    trainer::default::outputs::OutputBuckets,
+};
+
+/// Output buckets chosen by the number of pieces on the board, including kings.
+#[derive(Clone, Copy, Default)]
+pub struct MaterialBuckets<const N: usize>;
+
+impl<const N: usize> MaterialBuckets<N> {
+    pub fn bucket_of(occupied: u32) -> usize {
+        let divisor = 32usize.div_ceil(N);
+        (occupied.saturating_sub(2) as usize / divisor).min(N - 1)
+    }
+}
+
+impl<const N: usize> OutputBuckets<ChessBoard> for MaterialBuckets<N> {
+    const BUCKETS: usize = N;
+
+    fn bucket(&self, pos: &ChessBoard) -> u8 {
+        Self::bucket_of(pos.occ().count_ones()) as u8
+    }
+}
+
+/// Output buckets chosen by game phase, counting 1 per minor, 2 per rook and
+/// 4 per queen, for a maximum of 24 in the starting position.
+#[derive(Clone, Copy, Default)]
+pub struct PhaseBuckets<const N: usize>;
+
+impl<const N: usize> PhaseBuckets<N> {
+    const WEIGHTS: [usize; 8] = [0, 0, 0, 1, 1, 2, 4, 0];
+
+    pub fn bucket_of(phase: usize) -> usize {
+        (phase.min(24) * N / 25).min(N - 1)
+    }
+
+    pub fn phase_of(bbs: &[u64; 8]) -> usize {
+        (Piece::KNIGHT..=Piece::QUEEN).map(|pc| Self::WEIGHTS[pc] * bbs[pc].count_ones() as usize).sum()
+    }
+}
+
+impl<const N: usize> OutputBuckets<ChessBoard> for PhaseBuckets<N> {
+    const BUCKETS: usize = N;
+
+    fn bucket(&self, pos: &ChessBoard) -> u8 {
+        let phase = pos.into_iter().map(|(pc, _)| Self::WEIGHTS[2 + usize::from(pc & 7)]).sum();
+        Self::bucket_of(phase) as u8
+    }
+}
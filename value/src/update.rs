@@ -0,0 +1,186 @@
+use bullet::default::formats::montyformat::chess::{Attacks, Castling, Move, Piece, Position, Side};
+
+use crate::input::{flip_horizontal, map_bb, map_features_where, mirror_and_bucket, ThreatInputs};
+
+/// How to bring one perspective's `l0` accumulator up to date after a move.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AccumulatorUpdate {
+    /// The king changed mirroring or bucket, so the accumulator has to be
+    /// rebuilt from scratch from these features.
+    Refresh(Vec<usize>),
+    /// Subtract the weights of `removed` and add the weights of `added`.
+    Delta { added: Vec<usize>, removed: Vec<usize> },
+}
+
+/// Board as seen by `side`: `[ours, theirs, pawns, ..., kings]`, flipped
+/// vertically for black, matching how `ChessBoard` orients the side to move.
+pub fn perspective_bbs(pos: &Position, side: usize) -> [u64; 8] {
+    let mut bbs = pos.bbs();
+
+    if side == Side::BLACK {
+        bbs = bbs.map(u64::swap_bytes);
+        bbs.swap(Side::WHITE, Side::BLACK);
+    }
+
+    bbs
+}
+
+/// All active features of `pos` from the perspective of `side`. The network
+/// is evaluated using the perspective of the side to move.
+pub fn perspective_features(inputs: &ThreatInputs, pos: &Position, side: usize) -> Vec<usize> {
+    let mut feats = Vec::new();
+    inputs.map_bbs(perspective_bbs(pos, side), |feat| feats.push(feat));
+    feats
+}
+
+/// Computes the accumulator updates for both perspectives, indexed by side,
+/// caused by playing `mov` in `pos`.
+///
+/// Only features whose attacker or target could have changed are recomputed:
+/// those involving a square whose contents changed, and those of sliders whose
+/// attacks pass through such a square before or after the move.
+pub fn threat_updates(inputs: &ThreatInputs, pos: &Position, castling: &Castling, mov: Move) -> [AccumulatorUpdate; 2] {
+    let mut next = *pos;
+    next.make(mov, castling);
+
+    [Side::WHITE, Side::BLACK].map(|side| {
+        let before = perspective_bbs(pos, side);
+        let after = perspective_bbs(&next, side);
+
+        let (mirror, bucket) = mirror_and_bucket(&before, inputs.buckets());
+
+        if (mirror, bucket) != mirror_and_bucket(&after, inputs.buckets()) {
+            let mut feats = Vec::new();
+            inputs.map_bbs(after, |feat| feats.push(feat));
+            return AccumulatorUpdate::Refresh(feats);
+        }
+
+        let mut changed = 0;
+        for (b, a) in before.iter().zip(after.iter()) {
+            changed |= b ^ a;
+        }
+
+        let orient = |bb: u64| if mirror { flip_horizontal(bb) } else { bb };
+        let changed = orient(changed);
+        let attackers =
+            changed | affected_sliders(before.map(orient), changed) | affected_sliders(after.map(orient), changed);
+
+        let keep = |src: usize, dest: usize| attackers & (1 << src) > 0 || changed & (1 << dest) > 0;
+
+        let mut removed = Vec::new();
+        let mut added = Vec::new();
+        map_features_where(before, inputs.buckets(), keep, |feat| removed.push(feat));
+        map_features_where(after, inputs.buckets(), keep, |feat| added.push(feat));
+
+        removed.sort_unstable();
+        added.sort_unstable();
+
+        let removed_only = removed.iter().copied().filter(|feat| added.binary_search(feat).is_err()).collect();
+        let added_only = added.iter().copied().filter(|feat| removed.binary_search(feat).is_err()).collect();
+
+        AccumulatorUpdate::Delta { added: added_only, removed: removed_only }
+    })
+}
+
+/// Sliders whose attack set touches any of the `changed` squares, for a board
+/// already in the mirrored frame.
+fn affected_sliders(bbs: [u64; 8], changed: u64) -> u64 {
+    let occ = bbs[0] | bbs[1];
+    let diag = bbs[Piece::BISHOP] | bbs[Piece::QUEEN];
+    let orth = bbs[Piece::ROOK] | bbs[Piece::QUEEN];
+
+    let mut affected = 0;
+
+    map_bb(diag, |sq| {
+        if Attacks::bishop(sq, occ) & changed > 0 {
+            affected |= 1 << sq;
+        }
+    });
+
+    map_bb(orth, |sq| {
+        if Attacks::rook(sq, occ) & changed > 0 {
+            affected |= 1 << sq;
+        }
+    });
+
+    affected
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use montytrain_common::rng::Rand;
+
+    use super::*;
+    use crate::input::{KING_BUCKETS_4, KING_BUCKETS_8, NO_BUCKETS};
+
+    const STARTPOS: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    const GAMES: usize = 16;
+    const MAX_PLIES: usize = 128;
+
+    fn features(inputs: &ThreatInputs, pos: &Position, side: usize) -> BTreeSet<usize> {
+        perspective_features(inputs, pos, side).into_iter().collect()
+    }
+
+    /// Plays random games and checks that applying the update for every legal
+    /// move gives the same accumulator as a full recompute.
+    #[test]
+    fn updates_match_recompute() {
+        let mut rng = Rand::new(24301);
+
+        for layout in [NO_BUCKETS, KING_BUCKETS_4, KING_BUCKETS_8] {
+            let inputs = ThreatInputs::new(layout);
+
+            for _ in 0..GAMES {
+                let mut castling = Castling::default();
+                let mut pos = Position::parse_fen(STARTPOS, &mut castling);
+
+                for _ in 0..MAX_PLIES {
+                    let mut moves = Vec::new();
+                    pos.map_legal_moves(&castling, |mov| moves.push(mov));
+
+                    if moves.is_empty() {
+                        break;
+                    }
+
+                    let accs = [Side::WHITE, Side::BLACK].map(|side| features(&inputs, &pos, side));
+
+                    for &mov in &moves {
+                        let mut next = pos;
+                        next.make(mov, &castling);
+
+                        let updates = threat_updates(&inputs, &pos, &castling, mov);
+
+                        for side in [Side::WHITE, Side::BLACK] {
+                            let mut acc = accs[side].clone();
+
+                            match &updates[side] {
+                                AccumulatorUpdate::Refresh(feats) => acc = feats.iter().copied().collect(),
+                                AccumulatorUpdate::Delta { added, removed } => {
+                                    for feat in removed {
+                                        assert!(acc.remove(feat), "removed inactive feature {feat}");
+                                    }
+
+                                    for feat in added {
+                                        assert!(acc.insert(*feat), "added active feature {feat}");
+                                    }
+                                }
+                            }
+
+                            assert_eq!(
+                                acc,
+                                features(&inputs, &next, side),
+                                "{} after {}",
+                                pos.as_fen(),
+                                mov.to_uci(&castling)
+                            );
+                        }
+                    }
+
+                    pos.make(moves[rng.rand() as usize % moves.len()], &castling);
+                }
+            }
+        }
+    }
+}